@@ -1,77 +1,405 @@
 use tui::widgets::{Block, Borders, List, Text, Paragraph, ListState};
 use tui::widgets::canvas::{Canvas, Points};
-use tui::layout::{Layout, Constraint, Alignment, Direction};
+use tui::layout::{Layout, Constraint, Alignment, Direction, Rect};
 use tui::style::{Style, Color};
 use tui::{Frame, backend};
 use crossterm::event::KeyCode;
 
-use crate::computer::{Computer, SCR_ADDRESS, KBD_ADDRESS};
-use crate::assembler::to_asm;
-use crate::utils::get_bit;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::ops::Range;
+
+use hack_cpu_emulator::assembler::{assemble, predefined_symbols, Symbol, SymbolKind};
+use hack_cpu_emulator::computer::{Computer, SCR_ADDRESS, KBD_ADDRESS, ROM_SIZE};
+use hack_cpu_emulator::disassembler::{disassemble, Syntax};
+use hack_cpu_emulator::expression::Expression;
+use hack_cpu_emulator::format::{character, NumberFormat};
+use hack_cpu_emulator::loader::{Program, Source};
+use hack_cpu_emulator::trace::TraceWriter;
+use hack_cpu_emulator::utils::{get_bit, get_bit_slice};
+use hack_cpu_emulator::watchpoint::Watchpoint;
+
+use crate::cli::DEFAULT_SPEED;
+
+const MAX_SPEED: usize = 1 << 20;
+
+#[derive(Eq, PartialEq, Clone, Copy)]
+enum Prompt {
+    Memory,
+    Cycle,
+    Goto,
+    Search,
+    Watchpoint,
+    Condition
+}
 
 #[derive(Eq, PartialEq)]
 enum InputMode {
     Normal,
-    Editing,
+    Editing(Prompt),
     Keyboard
 }
 
+/// How a panel shows the values in it
+#[derive(Eq, PartialEq, Clone, Copy)]
+enum View {
+    Number(NumberFormat),
+    Asm(Syntax),
+    Char,
+    /// The assembly source, for programs that were assembled on load
+    Source
+}
+
+const ROM_VIEWS: [View; 8] = [
+    View::Source,
+    View::Asm(Syntax::Mnemonic),
+    View::Asm(Syntax::ControlBits),
+    View::Number(NumberFormat::Decimal),
+    View::Number(NumberFormat::Unsigned),
+    View::Number(NumberFormat::Hex),
+    View::Number(NumberFormat::Binary),
+    View::Char
+];
+const RAM_VIEWS: [View; 6] = [
+    View::Number(NumberFormat::Decimal),
+    View::Number(NumberFormat::Unsigned),
+    View::Number(NumberFormat::Hex),
+    View::Number(NumberFormat::Binary),
+    View::Asm(Syntax::Mnemonic),
+    View::Char
+];
+const REGISTER_VIEWS: [View; 4] = [
+    View::Number(NumberFormat::Decimal),
+    View::Number(NumberFormat::Unsigned),
+    View::Number(NumberFormat::Hex),
+    View::Number(NumberFormat::Binary)
+];
+
+impl View {
+    fn format(self, value: i16) -> String {
+        match self {
+            View::Number(format) => format.format(value),
+            View::Asm(syntax) => disassemble(value, syntax),
+            View::Char => character(value).unwrap_or_default(),
+            // Source lines aren't tied to values, so single values are disassembled
+            View::Source => disassemble(value, Syntax::Mnemonic)
+        }
+    }
+
+    /// Parses a value entered while this view is shown: a number in its base,
+    /// an instruction or a single character.
+    fn parse(self, input: &str) -> Option<i16> {
+        match self {
+            View::Number(format) => format.parse(input),
+            View::Asm(_) => match assemble(&[input.to_owned()]) {
+                Ok(assembly) if assembly.code.len() == 1 => Some(assembly.code[0]),
+                _ => None
+            }
+            View::Char => {
+                let mut chars = input.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if (c as u32) < 0x8000 => Some(c as u32 as i16),
+                    _ => None
+                }
+            }
+            View::Source => None
+        }
+    }
+
+    /// Whether `c` can be part of a value entered while this view is shown.
+    fn accepts(self, c: char) -> bool {
+        match self {
+            View::Number(NumberFormat::Decimal) => c.is_ascii_digit() || c == '-',
+            View::Number(NumberFormat::Unsigned) => c.is_ascii_digit(),
+            View::Number(NumberFormat::Hex) => c.is_ascii_hexdigit() || c == 'x' || c == 'X',
+            View::Number(NumberFormat::Binary) => c == '0' || c == '1' || c == 'b',
+            View::Asm(_) | View::Char => true,
+            View::Source => false
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            View::Number(format) => format.name(),
+            View::Asm(Syntax::Mnemonic) => "asm",
+            View::Asm(Syntax::ControlBits) => "bits",
+            View::Char => "char",
+            View::Source => "source"
+        }
+    }
+
+    fn next(self, views: &[View]) -> View {
+        let i = views.iter().position(|&view| view == self).unwrap_or(0);
+        views[(i + 1) % views.len()]
+    }
+}
+
+/// Where continuous execution should stop, besides breakpoints
+#[derive(Eq, PartialEq, Clone, Copy)]
+enum RunUntil {
+    Address(usize),
+    /// The instruction after a jump is reached with the stack pointer no
+    /// higher than it was, so recursive VM calls are stepped over entirely
+    Return { address: usize, stack_pointer: i16 },
+    JumpTaken,
+    Cycle(u64)
+}
+
+#[derive(Eq, PartialEq, Clone, Copy)]
+enum Focus {
+    Rom,
+    Ram,
+    Breakpoints
+}
+
 pub struct App {
     filename: String,
     computer: Computer,
+    source: Option<Source>,
+    symbols: Vec<Symbol>,
     rom_cursor: ListState,
     ram_cursor: ListState,
+    watch_cursor: ListState,
+    /// Names of the RAM addresses the program's symbols refer to
+    ram_names: BTreeMap<usize, String>,
+    /// Whether the program was translated from VM code, so RAM[0] is the stack pointer
+    uses_vm_stack: bool,
+    is_watching: bool,
+    /// Colour ROM and RAM rows by how often they were executed or accessed
+    is_heat_view: bool,
+    /// Show the RAM accesses of the whole address space instead of the screen
+    is_memory_map: bool,
+    breakpoint_cursor: ListState,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: Vec<Watchpoint>,
+    /// Conditional breakpoints, checked after every instruction that makes
+    /// them true
+    conditions: Vec<Expression>,
+    /// The instruction that last triggered a watchpoint
+    watch_hit_at: Option<usize>,
+    focus: Focus,
+    rom_view: View,
+    ram_view: View,
+    register_view: View,
     input: String,
     input_mode: InputMode,
+    last_search: Option<String>,
+    /// Shown in the status bar until the next key press
+    message: Option<String>,
+    /// Rows visible in the ROM and RAM panels, for paging
+    page_size: usize,
+    /// The first rows shown in the ROM and RAM panels. Only the visible rows
+    /// are built, so the panels scroll themselves rather than leaving it to `List`
+    rom_offset: usize,
+    ram_offset: usize,
     pub cursor_pos: Option<(u16, u16)>,
-    is_full_screen: bool
+    is_full_screen: bool,
+    is_running: bool,
+    run_until: Option<RunUntil>,
+    speed: usize,
+    trace: Option<TraceWriter<BufWriter<File>>>
 }
 
 impl App {
-    pub fn new(filename: String, program: Vec<i16>) -> App {
+    pub fn new(filename: String, program: Program) -> App {
         let mut rom_cursor = ListState::default();
         rom_cursor.select(Some(0));
 
         let mut ram_cursor = ListState::default();
         ram_cursor.select(Some(0));
 
+        let mut watch_cursor = ListState::default();
+        watch_cursor.select(Some(0));
+
         let mut computer = Computer::new();
-        for (i, instr) in program.iter().enumerate() {
-            computer.rom[i] = Some(*instr);
+        computer.load(&program.code).expect("the loader rejects programs larger than ROM");
+
+        let symbols = match &program.source {
+            Some(source) => source.symbols.clone(),
+            None => predefined_symbols()
+        };
+        // VM translators name return addresses and function labels `f$...`
+        let uses_vm_stack = symbols.iter()
+            .any(|symbol| symbol.kind == SymbolKind::Label && (symbol.name.contains('$') || symbol.name == "Sys.init"));
+        let mut ram_symbols = symbols.clone();
+        ram_symbols.retain(|symbol| symbol.kind != SymbolKind::Label && (symbol.value as usize) < computer.memory.len());
+        ram_symbols.sort_by(|a, b| b.kind.cmp(&a.kind).then_with(|| a.name.cmp(&b.name)));
+        let mut ram_names: BTreeMap<usize, String> = BTreeMap::new();
+        for symbol in ram_symbols {
+            ram_names.entry(symbol.value as usize)
+                .and_modify(|names| *names = format!("{}/{}", names, symbol.name))
+                .or_insert(symbol.name);
         }
 
+        let rom_view = if program.source.is_some() { View::Source } else { View::Asm(Syntax::Mnemonic) };
+
         App {
             filename,
             computer,
+            source: program.source,
+            symbols,
             rom_cursor,
             ram_cursor,
+            watch_cursor,
+            ram_names,
+            uses_vm_stack,
+            is_watching: false,
+            is_heat_view: false,
+            is_memory_map: false,
+            breakpoint_cursor: ListState::default(),
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            conditions: Vec::new(),
+            watch_hit_at: None,
+            focus: Focus::Ram,
+            rom_view,
+            ram_view: RAM_VIEWS[0],
+            register_view: REGISTER_VIEWS[0],
             input: String::new(),
             input_mode: InputMode::Normal,
+            last_search: None,
+            message: None,
+            page_size: 1,
+            rom_offset: 0,
+            ram_offset: 0,
             cursor_pos: None,
-            is_full_screen: false
+            is_full_screen: false,
+            is_running: false,
+            run_until: None,
+            speed: DEFAULT_SPEED,
+            trace: None
+        }
+    }
+
+    pub fn set_history_size(&mut self, size: usize) {
+        self.computer.set_history_size(size);
+    }
+
+    pub fn set_speed(&mut self, speed: usize) {
+        self.speed = speed.clamp(1, MAX_SPEED);
+    }
+
+    /// Records every instruction executed from now on.
+    pub fn set_trace(&mut self, trace: TraceWriter<BufWriter<File>>) {
+        self.trace = Some(trace);
+    }
+
+    pub fn finish_trace(&mut self) -> io::Result<()> {
+        match self.trace.take() {
+            Some(trace) => trace.finish(),
+            None => Ok(())
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.is_running
+    }
+
+    pub fn tick(&mut self) {
+        if self.is_running {
+            for _ in 0..self.speed {
+                let pc = self.computer.pc;
+                if !self.step() {
+                    break;
+                }
+                let new_pc = self.computer.pc as u16 as usize;
+                let reached = match self.run_until {
+                    Some(RunUntil::Address(address)) => new_pc == address,
+                    Some(RunUntil::Return { address, stack_pointer }) => {
+                        new_pc == address && self.computer.memory[0] <= stack_pointer
+                    }
+                    Some(RunUntil::JumpTaken) => self.computer.pc != pc.wrapping_add(1),
+                    Some(RunUntil::Cycle(cycle)) => self.computer.cycles >= cycle,
+                    None => false
+                };
+                if reached || self.breakpoints.contains(&new_pc) {
+                    self.is_running = false;
+                    break;
+                }
+            }
+            if !self.is_running {
+                self.run_until = None;
+            }
+            self.follow_pc();
         }
     }
 
     pub fn clear_input_event(&mut self) {
-        match self.input_mode {
-            InputMode::Keyboard => self.computer.memory[KBD_ADDRESS] = 0,
-            _ => {}
+        if self.input_mode == InputMode::Keyboard {
+            self.computer.memory[KBD_ADDRESS] = 0;
         }
     }
 
     pub fn handle_input_event(&mut self, event: KeyCode) -> bool {
+        if self.input_mode == InputMode::Normal {
+            self.message = None;
+        }
         match self.input_mode {
-            InputMode::Editing => match event {
-                KeyCode::Char(c @ '0'..='9') | KeyCode::Char(c @ '-') => {
-                    self.input.push(c);
+            InputMode::Editing(prompt) => match event {
+                KeyCode::Char(c) => {
+                    let accepted = match prompt {
+                        Prompt::Memory => self.ram_view.accepts(c),
+                        Prompt::Cycle => c.is_ascii_digit(),
+                        // A decimal or hex address, or a symbol
+                        Prompt::Goto => c.is_ascii_alphanumeric() || "_.$:".contains(c),
+                        Prompt::Search | Prompt::Watchpoint | Prompt::Condition => true
+                    };
+                    if accepted {
+                        self.input.push(c);
+                    }
                 }
                 KeyCode::Backspace => {
                     self.input.pop();
                 }
                 KeyCode::Enter => {
                     let input: String = self.input.drain(..).collect();
-                    let cursor = self.ram_cursor.selected().unwrap_or(0);
-                    self.computer.memory[cursor] = input.parse().unwrap();
+                    match prompt {
+                        Prompt::Memory => match self.ram_view.parse(&input) {
+                            Some(value) => {
+                                let address = self.selected_ram_address();
+                                self.computer.memory[address] = value;
+                            }
+                            None if input.is_empty() => {}
+                            None => self.message = Some(format!("invalid {} value: {}", self.ram_view.name(), input))
+                        }
+                        Prompt::Cycle => match input.parse() {
+                            Ok(cycle) => self.jump_to_cycle(cycle),
+                            Err(_) if input.is_empty() => {}
+                            Err(_) => self.message = Some(format!("invalid cycle: {}", input))
+                        }
+                        Prompt::Goto => self.goto(&input),
+                        Prompt::Search => {
+                            self.search(&input);
+                            self.last_search = Some(input);
+                        }
+                        Prompt::Watchpoint => {
+                            let symbols = &self.symbols;
+                            let symbol = |name: &str| symbols.iter()
+                                .find(|symbol| symbol.name == name && symbol.kind != SymbolKind::Label)
+                                .map(|symbol| symbol.value);
+                            match Watchpoint::parse(&input, symbol) {
+                                Ok(watchpoint) => {
+                                    self.watchpoints.push(watchpoint);
+                                    self.clamp_breakpoint_cursor();
+                                }
+                                Err(error) => self.message = Some(error)
+                            }
+                        }
+                        Prompt::Condition => {
+                            let symbols = &self.symbols;
+                            let symbol = |name: &str| symbols.iter()
+                                .find(|symbol| symbol.name == name)
+                                .map(|symbol| symbol.value);
+                            match Expression::parse(&input, symbol) {
+                                Ok(condition) => {
+                                    self.conditions.push(condition);
+                                    self.clamp_breakpoint_cursor();
+                                }
+                                Err(error) => self.message = Some(error)
+                            }
+                        }
+                    }
                     self.input_mode = InputMode::Normal;
                 }
                 KeyCode::Esc => {
@@ -82,26 +410,145 @@ impl App {
             }
             InputMode::Normal => match event {
                 KeyCode::Char('n') => {
-                    self.computer.step();
-                    self.rom_cursor.select(Some(self.computer.pc as usize));
+                    self.step();
+                    self.follow_pc();
+                }
+                KeyCode::Char('p') => {
+                    self.is_running = false;
+                    self.computer.step_back();
+                    self.follow_pc();
+                }
+                KeyCode::Char('P') => {
+                    self.is_running = false;
+                    while self.computer.step_back() {
+                        if self.breakpoints.contains(&(self.computer.pc as usize)) {
+                            break;
+                        }
+                    }
+                    self.follow_pc();
+                }
+                KeyCode::Char('t') => {
+                    self.is_running = false;
+                    self.input_mode = InputMode::Editing(Prompt::Cycle);
+                }
+                KeyCode::Char('c') => {
+                    self.is_running = !self.is_running;
+                    self.run_until = None;
+                }
+                KeyCode::Char('o') => {
+                    self.step_over();
+                }
+                KeyCode::Char('u') => {
+                    let address = self.rom_cursor.selected().unwrap_or(0);
+                    self.run(RunUntil::Address(address));
+                }
+                KeyCode::Char('J') => {
+                    self.run(RunUntil::JumpTaken);
+                }
+                KeyCode::Char('+') | KeyCode::Char('=') => {
+                    self.speed = (self.speed * 2).min(MAX_SPEED);
+                }
+                KeyCode::Char('-') => {
+                    self.speed = (self.speed / 2).max(1);
+                }
+                KeyCode::Char('v') => match self.focus {
+                    Focus::Rom | Focus::Breakpoints => self.rom_view = self.rom_view.next(self.rom_views()),
+                    Focus::Ram => self.ram_view = self.ram_view.next(&RAM_VIEWS)
+                }
+                KeyCode::Char('V') => {
+                    self.register_view = self.register_view.next(&REGISTER_VIEWS);
+                }
+                KeyCode::Char('w') => {
+                    self.is_watching = !self.is_watching;
+                }
+                // Counting slows execution down, so it starts once a view needs it
+                KeyCode::Char('h') => {
+                    self.is_heat_view = !self.is_heat_view;
+                    self.computer.set_profiling(true);
+                }
+                KeyCode::Char('m') => {
+                    self.is_memory_map = !self.is_memory_map;
+                    self.computer.set_profiling(true);
                 }
                 KeyCode::Char('f') => {
                     self.is_full_screen = !self.is_full_screen;
                 }
+                KeyCode::Tab => {
+                    self.focus = match self.focus {
+                        Focus::Rom => Focus::Ram,
+                        Focus::Ram => Focus::Breakpoints,
+                        Focus::Breakpoints => Focus::Rom
+                    };
+                }
                 KeyCode::Char('j') => {
-                    if let Some(i) = self.ram_cursor.selected() {
-                        self.ram_cursor.select(Some(i + 1));
-                    }
+                    self.move_cursor(1);
                 }
                 KeyCode::Char('k') => {
-                    if let Some(i) = self.ram_cursor.selected() {
-                        if i != 0 {
-                            self.ram_cursor.select(Some(i-1));
+                    self.move_cursor(-1);
+                }
+                KeyCode::PageDown => {
+                    self.move_cursor(self.page_size as isize);
+                }
+                KeyCode::PageUp => {
+                    self.move_cursor(-(self.page_size as isize));
+                }
+                KeyCode::Home => {
+                    self.set_cursor(0);
+                }
+                KeyCode::End => {
+                    self.set_cursor(usize::MAX);
+                }
+                KeyCode::Char('g') => {
+                    self.input_mode = InputMode::Editing(Prompt::Goto);
+                }
+                KeyCode::Char('/') => {
+                    self.input_mode = InputMode::Editing(Prompt::Search);
+                }
+                KeyCode::Char('N') => {
+                    if let Some(pattern) = self.last_search.clone() {
+                        self.search(&pattern);
+                    }
+                }
+                KeyCode::Char('x') => match self.focus {
+                    Focus::Rom => {
+                        let address = self.rom_cursor.selected().unwrap_or(0);
+                        if !self.breakpoints.remove(&address) {
+                            self.breakpoints.insert(address);
+                        }
+                        self.clamp_breakpoint_cursor();
+                    }
+                    Focus::Breakpoints => {
+                        // Breakpoints are listed first, then watchpoints and conditions
+                        if let Some(i) = self.breakpoint_cursor.selected() {
+                            let watchpoints = self.breakpoints.len();
+                            let conditions = watchpoints + self.watchpoints.len();
+                            if i < watchpoints {
+                                let address = *self.breakpoints.iter().nth(i).unwrap();
+                                self.breakpoints.remove(&address);
+                            } else if i < conditions {
+                                self.watchpoints.remove(i - watchpoints);
+                            } else if i - conditions < self.conditions.len() {
+                                self.conditions.remove(i - conditions);
+                            }
                         }
+                        self.clamp_breakpoint_cursor();
                     }
+                    Focus::Ram => {}
+                }
+                KeyCode::Char('W') => {
+                    self.input_mode = InputMode::Editing(Prompt::Watchpoint);
+                }
+                KeyCode::Char('C') => {
+                    self.input_mode = InputMode::Editing(Prompt::Condition);
+                }
+                KeyCode::Char('X') => {
+                    self.breakpoints.clear();
+                    self.watchpoints.clear();
+                    self.conditions.clear();
+                    self.clamp_breakpoint_cursor();
                 }
                 KeyCode::Char('r') => {
-                    self.input_mode = InputMode::Editing;
+                    self.input_mode = InputMode::Editing(Prompt::Memory);
                 }
                 KeyCode::Char('b') => {
                     self.input_mode = InputMode::Keyboard;
@@ -124,6 +571,217 @@ impl App {
         false
     }
 
+    /// Executes one instruction, pausing execution if it faults, triggers a
+    /// watchpoint or makes a conditional breakpoint true.
+    fn step(&mut self) -> bool {
+        let pc = self.computer.pc as u16 as usize;
+        self.watch_hit_at = None;
+        // Conditions only pause when they turn true, so resuming from one that
+        // still holds doesn't stop again straight away
+        let held: Vec<bool> = self.conditions.iter().map(|condition| condition.is_true(&self.computer)).collect();
+        let result = match &mut self.trace {
+            Some(trace) => trace.step(&mut self.computer),
+            None => self.computer.step()
+        };
+        let access = match result {
+            Ok(access) => access,
+            Err(_) => {
+                self.is_running = false;
+                return false;
+            }
+        };
+        if let Some(hit) = self.watchpoints.iter().find_map(|watchpoint| watchpoint.check(&access)) {
+            self.is_running = false;
+            self.watch_hit_at = Some(pc);
+            self.message = Some(format!("watchpoint: instruction {} {}", pc, hit));
+            return false;
+        }
+        let computer = &self.computer;
+        let became_true = self.conditions.iter().zip(held)
+            .find(|(condition, held)| !held && condition.is_true(computer));
+        if let Some((condition, _)) = became_true {
+            self.is_running = false;
+            self.message = Some(format!("breakpoint: {}", condition));
+            return false;
+        }
+        true
+    }
+
+    fn run(&mut self, until: RunUntil) {
+        self.run_until = Some(until);
+        self.is_running = true;
+    }
+
+    /// Runs past a jump until execution comes back to the next instruction,
+    /// e.g. out of a subroutine or a VM `call`. Other instructions are
+    /// simply stepped.
+    fn step_over(&mut self) {
+        let pc = self.computer.pc as u16 as usize;
+        let is_jump = match self.computer.rom.get(pc) {
+            Some(Some(instr)) => get_bit(*instr, 15) && get_bit_slice(*instr, 0, 3) != 0,
+            _ => false
+        };
+        if is_jump && self.uses_vm_stack {
+            let stack_pointer = self.computer.memory[0];
+            self.run(RunUntil::Return { address: pc + 1, stack_pointer });
+        } else if is_jump {
+            self.run(RunUntil::Address(pc + 1));
+        } else {
+            self.step();
+            self.follow_pc();
+        }
+    }
+
+    fn follow_pc(&mut self) {
+        let pc = (self.computer.pc as u16 as usize).min(ROM_SIZE - 1);
+        self.rom_cursor.select(Some(pc));
+    }
+
+    /// Rewinds to a past cycle, or runs to a future one a frame at a time,
+    /// stopping at breakpoints on the way like continuous execution does.
+    fn jump_to_cycle(&mut self, cycle: u64) {
+        if cycle < self.computer.cycles {
+            if !self.computer.rewind_to(cycle) {
+                self.message = Some(format!(
+                    "cycle {} is older than the oldest recorded cycle {}",
+                    cycle,
+                    self.computer.oldest_cycle()
+                ));
+            }
+            self.follow_pc();
+        } else if cycle > self.computer.cycles {
+            self.run(RunUntil::Cycle(cycle));
+        }
+    }
+
+    fn focused_cursor(&mut self) -> (&mut ListState, usize) {
+        match self.focus {
+            Focus::Rom => {
+                let len = match (self.rom_view, &self.source) {
+                    (View::Source, Some(source)) => source.source_map.len(),
+                    _ => self.computer.rom.len()
+                };
+                (&mut self.rom_cursor, len)
+            }
+            Focus::Ram if self.is_watching => (&mut self.watch_cursor, self.ram_names.len()),
+            Focus::Ram => (&mut self.ram_cursor, self.computer.memory.len()),
+            Focus::Breakpoints => {
+                let len = self.breakpoints.len() + self.watchpoints.len() + self.conditions.len();
+                (&mut self.breakpoint_cursor, len)
+            }
+        }
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        let (cursor, _) = self.focused_cursor();
+        let i = cursor.selected().unwrap_or(0) as isize + delta;
+        self.set_cursor(i.max(0) as usize);
+    }
+
+    /// Selects the given row of the focused panel, clamped to its length.
+    fn set_cursor(&mut self, index: usize) {
+        let (cursor, len) = self.focused_cursor();
+        if len > 0 {
+            cursor.select(Some(index.min(len - 1)));
+        }
+    }
+
+    /// Moves the ROM or RAM cursor to an address or to a symbol: labels for
+    /// ROM, variables and predefined symbols for RAM.
+    fn goto(&mut self, input: &str) {
+        if self.focus == Focus::Breakpoints {
+            self.focus = Focus::Rom;
+        }
+        let is_rom = self.focus == Focus::Rom;
+        let input = input.trim();
+        let address = if input.starts_with("0x") {
+            NumberFormat::Hex.parse(input).map(|value| value as u16 as usize)
+        } else {
+            input.parse().ok()
+        };
+        let address = address.or_else(|| {
+            self.symbols.iter()
+                .find(|symbol| symbol.name == input && (symbol.kind == SymbolKind::Label) == is_rom)
+                .map(|symbol| symbol.value as usize)
+        });
+        match address {
+            Some(address) => {
+                if !is_rom {
+                    self.is_watching = false;
+                }
+                self.set_cursor(address);
+            }
+            None => self.message = Some(format!("unknown address or symbol: {}", input))
+        }
+    }
+
+    /// Selects the next row of the focused panel after the cursor that matches
+    /// `pattern`, wrapping around. ROM rows match if their text contains the
+    /// pattern; RAM rows if their value equals it or their name contains it.
+    fn search(&mut self, pattern: &str) {
+        let pattern = pattern.trim();
+        let (cursor, len) = self.focused_cursor();
+        let start = cursor.selected().map_or(0, |i| i + 1);
+        if pattern.is_empty() || len == 0 || self.focus == Focus::Breakpoints {
+            return;
+        }
+        let value = self.ram_view.parse(pattern);
+        let watched: Vec<usize> = self.ram_names.keys().copied().collect();
+        let matches = |i: usize| match self.focus {
+            Focus::Rom => {
+                let text = match (self.rom_view, &self.source) {
+                    (View::Source, Some(source)) => source.lines[source.source_map[i]].clone(),
+                    _ => self.computer.rom[i].map(|v| self.rom_view.format(v)).unwrap_or_default()
+                };
+                text.contains(pattern)
+            }
+            _ => {
+                let address = if self.is_watching { watched[i] } else { i };
+                let name_matches = matches!(self.ram_names.get(&address), Some(names) if names.contains(pattern));
+                value == Some(self.computer.memory[address]) || name_matches
+            }
+        };
+        match (start..len).chain(0..start).find(|&i| matches(i)) {
+            Some(i) => self.set_cursor(i),
+            None => self.message = Some(format!("not found: {}", pattern))
+        }
+    }
+
+    fn clamp_breakpoint_cursor(&mut self) {
+        let len = self.breakpoints.len() + self.watchpoints.len() + self.conditions.len();
+        let selected = match self.breakpoint_cursor.selected() {
+            _ if len == 0 => None,
+            Some(i) => Some(i.min(len - 1)),
+            None => Some(0)
+        };
+        self.breakpoint_cursor.select(selected);
+    }
+
+    fn selected_ram_address(&self) -> usize {
+        if self.is_watching {
+            let selected = self.watch_cursor.selected().unwrap_or(0);
+            self.ram_names.keys().nth(selected).copied().unwrap_or(0)
+        } else {
+            self.ram_cursor.selected().unwrap_or(0)
+        }
+    }
+
+    fn rom_views(&self) -> &'static [View] {
+        match self.source {
+            Some(_) => &ROM_VIEWS,
+            None => &ROM_VIEWS[1..]
+        }
+    }
+
+    fn panel_block<'a>(&self, title: &'a str, focus: Focus) -> Block<'a> {
+        let border_style = if self.focus == focus {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        Block::default().title(title).borders(Borders::ALL).border_style(border_style)
+    }
+
     pub fn draw<B: backend::Backend>(&mut self, f: &mut Frame<B>) {
         let rows = Layout::default()
             .direction(Direction::Vertical)
@@ -145,69 +803,216 @@ impl App {
             .constraints(vec![Constraint::Min(4), Constraint::Length(3), Constraint::Length(3)])
             .split(columns[1]);
         
+        // Rows of the ROM panel, less its borders
+        self.page_size = (column1[0].height as usize).saturating_sub(2).max(1);
+        let ram_height = (column2[0].height as usize).saturating_sub(2).max(1);
+
+        let source = match (self.rom_view, &self.source) {
+            (View::Source, Some(source)) => Some(source),
+            _ => None
+        };
+        let (rom_selected, rom_len) = match source {
+            // The cursor stays on a ROM address; show it on that address' line
+            Some(source) => {
+                let address = self.rom_cursor.selected().unwrap_or(0);
+                (source.source_map.get(address).copied(), source.lines.len())
+            }
+            None => (self.rom_cursor.selected(), self.computer.rom.len())
+        };
+        let rom_rows = visible_rows(&mut self.rom_offset, rom_selected, self.page_size, rom_len);
+        let (ram_selected, ram_len) = if self.is_watching {
+            (self.watch_cursor.selected(), self.ram_names.len())
+        } else {
+            (self.ram_cursor.selected(), self.computer.memory.len())
+        };
+        let ram_rows = visible_rows(&mut self.ram_offset, ram_selected, ram_height, ram_len);
+
         let column3 = Layout::default()
             .direction(Direction::Vertical)
             .constraints(vec![Constraint::Percentage(60), Constraint::Percentage(40)])
             .split(columns[2]);
 
-        let text = self.computer.rom.iter().enumerate()
-            .map(|(i, v)| {
-                let asm = match v {
-                    Some(v) => to_asm(*v),
-                    None => "".to_owned()
-                };
-                Text::raw(format!("{:5}| {}", i, asm))
-            });
-        let rom_block = List::new(text)
-            .block(Block::default().title("[ROM]").borders(Borders::ALL))
+        let breakpoints = &self.breakpoints;
+        let watch_hit_at = self.watch_hit_at;
+        let marker = |address: usize| match address {
+            _ if watch_hit_at == Some(address) => '!',
+            _ if breakpoints.contains(&address) => '*',
+            _ => ' '
+        };
+        let heat = if self.is_heat_view { self.computer.profile() } else { None };
+        let max_count = heat.and_then(|counts| counts.iter().max().copied()).unwrap_or(0);
+        let count = |address: usize| match heat {
+            Some(counts) => format!("{:>7} ", counts[address]),
+            None => "".to_owned()
+        };
+        let heat_style = |address: usize| match heat {
+            Some(counts) => Style::default().fg(heat_color(counts[address], max_count)),
+            None => Style::default()
+        };
+        let rom_view = self.rom_view;
+        let text: Vec<Text> = match source {
+            Some(source) => {
+                let mut addresses = vec![None; source.lines.len()];
+                for (address, &line) in source.source_map.iter().enumerate() {
+                    addresses[line] = Some(address);
+                }
+                let pc = self.computer.pc as u16 as usize;
+                source.lines[rom_rows.clone()].iter().zip(&addresses[rom_rows.clone()])
+                    .map(|(line, &address)| match address {
+                        Some(address) => {
+                            let row = format!("{}{:5}| {}{}", marker(address), address, count(address), line);
+                            if address == pc {
+                                Text::styled(row, Style::default().fg(Color::Green))
+                            } else {
+                                Text::styled(row, heat_style(address))
+                            }
+                        }
+                        None => Text::raw(format!("      | {:1$}{2}", "", count(0).len(), line))
+                    })
+                    .collect()
+            }
+            None => self.computer.rom[rom_rows.clone()].iter().zip(rom_rows.clone())
+                .map(|(v, i)| {
+                    let value = match v {
+                        Some(v) => rom_view.format(*v),
+                        None => "".to_owned()
+                    };
+                    Text::styled(format!("{}{:5}| {}{}", marker(i), i, count(i), value), heat_style(i))
+                })
+                .collect()
+        };
+        let rom_title = if heat.is_some() {
+            format!("[ROM: {}, heat]", rom_view.name())
+        } else {
+            format!("[ROM: {}]", rom_view.name())
+        };
+        let rom_block = List::new(text.into_iter())
+            .block(self.panel_block(&rom_title, Focus::Rom))
             .highlight_symbol(">")
             .highlight_style(Style::default().fg(Color::Yellow));
 
-        let text = self.computer.memory.iter().enumerate()
-            .map(|(i, v)| Text::raw(format!("{:5}| {}", i, v)));
-        let ram_block = List::new(text)
-            .block(Block::default().title("[RAM]").borders(Borders::ALL))
+        let ram_view = self.ram_view;
+        let memory = &self.computer.memory;
+        let ram_names = &self.ram_names;
+        let ram_heat = if self.is_heat_view { self.computer.memory_profile() } else { None };
+        let max_accesses = ram_heat
+            .and_then(|counts| counts.reads.iter().zip(&counts.writes).map(|(r, w)| r + w).max())
+            .unwrap_or(0);
+        let ram_row = |i: usize, text: String| match ram_heat {
+            Some(counts) => {
+                let (reads, writes) = (counts.reads[i], counts.writes[i]);
+                let row = format!("{:5}|{:>5}r{:>5}w {}", i, reads, writes, text);
+                Text::styled(row, Style::default().fg(heat_color(reads + writes, max_accesses)))
+            }
+            None => Text::raw(format!("{:5}| {}", i, text))
+        };
+        let text: Vec<Text> = if self.is_watching {
+            ram_names.iter()
+                .skip(ram_rows.start)
+                .take(ram_rows.len())
+                .map(|(&i, names)| ram_row(i, format!("{} = {}", names, ram_view.format(memory[i]))))
+                .collect()
+        } else {
+            memory[ram_rows.clone()].iter().zip(ram_rows.clone())
+                .map(|(v, i)| match ram_names.get(&i) {
+                    Some(names) => ram_row(i, format!("{}  {}", ram_view.format(*v), names)),
+                    None => ram_row(i, ram_view.format(*v))
+                })
+                .collect()
+        };
+        let heat_suffix = if ram_heat.is_some() { ", heat" } else { "" };
+        let ram_title = if self.is_watching {
+            format!("[Variables: {}{}]", ram_view.name(), heat_suffix)
+        } else {
+            format!("[RAM: {}{}]", ram_view.name(), heat_suffix)
+        };
+        let ram_block = List::new(text.into_iter())
+            .block(self.panel_block(&ram_title, Focus::Ram))
             .highlight_style(Style::default().fg(Color::Yellow));
 
-        let text = [Text::raw(self.computer.d_register.to_string())];
+        let rom = &self.computer.rom;
+        let text = self.breakpoints.iter()
+            .map(|&i| {
+                let value = match source.and_then(|source| source.source_map.get(i)) {
+                    Some(&line) => source.unwrap().lines[line].trim().to_owned(),
+                    None => rom[i].map(|v| rom_view.format(v)).unwrap_or_default()
+                };
+                Text::raw(format!("{:5}| {}", i, value))
+            })
+            .chain(self.watchpoints.iter().map(|watchpoint| Text::raw(format!("watch| {}", watchpoint))))
+            .chain(self.conditions.iter().map(|condition| Text::raw(format!("   if| {}", condition))));
+        let breakpoints_block = List::new(text)
+            .block(self.panel_block("[Breakpoints]", Focus::Breakpoints))
+            .highlight_symbol(">")
+            .highlight_style(Style::default().fg(Color::Yellow));
+
+        let register_view = self.register_view;
+        let text = [Text::raw(register_view.format(self.computer.d_register))];
+        let d_register_title = format!("[D Register: {}]", register_view.name());
         let d_register_block = Paragraph::new(text.iter())
-            .block(Block::default().title("[D Register]").borders(Borders::ALL))
+            .block(Block::default().title(&d_register_title).borders(Borders::ALL))
             .alignment(Alignment::Center);
 
-        let text = [Text::raw(self.computer.a_register.to_string())];
+        let text = [Text::raw(register_view.format(self.computer.a_register))];
+        let a_register_title = format!("[A Register: {}]", register_view.name());
         let a_register_block = Paragraph::new(text.iter())
-            .block(Block::default().title("[A Register]").borders(Borders::ALL))
+            .block(Block::default().title(&a_register_title).borders(Borders::ALL))
             .alignment(Alignment::Center);
 
-        let text = [Text::raw(self.computer.pc.to_string())];
+        let text = [Text::raw(register_view.format(self.computer.pc))];
+        let pc_title = format!("[PC: {}]", register_view.name());
         let pc_block = Paragraph::new(text.iter())
-            .block(Block::default().title("[PC]").borders(Borders::ALL))
+            .block(Block::default().title(&pc_title).borders(Borders::ALL))
             .alignment(Alignment::Center);
 
-        let dots = Points {coords: &self.get_screen_dots(), color: Color::White};
-        let screen_block = Canvas::default()
-            .block(Block::default().borders(Borders::ALL).title("[Screen]"))
-            .paint(|ctx| {
-                ctx.draw(&dots);
-            })
-            .x_bounds([0.0, 512.0])
-            .y_bounds([0.0, 256.0]);
-
         let (text, style, cursor_pos) = match self.input_mode {
-            InputMode::Editing => {
-                let prompt = format!(
-                    " Enter the new value at memory address ({}): {}",
-                    self.ram_cursor.selected().unwrap_or(0),
-                    self.input
-                );
+            InputMode::Editing(prompt) => {
+                let prompt = match prompt {
+                    Prompt::Memory => format!(
+                        " Enter the new value ({}) at memory address ({}): {}",
+                        self.ram_view.name(),
+                        self.selected_ram_address(),
+                        self.input
+                    ),
+                    Prompt::Cycle => format!(
+                        " Enter the cycle to jump to (earliest {}): {}",
+                        self.computer.oldest_cycle(),
+                        self.input
+                    ),
+                    Prompt::Goto => format!(" Go to address or symbol: {}", self.input),
+                    Prompt::Search => format!(" Search for: {}", self.input),
+                    Prompt::Watchpoint => format!(
+                        " Watch [r|w|rw] <address>[..<end>] [<comparison> <value>]: {}",
+                        self.input
+                    ),
+                    Prompt::Condition => format!(" Break when: {}", self.input)
+                };
                 let cursor_pos = Some((prompt.len() as u16, rows[1].y));
                 let text = [Text::raw(prompt)];
                 let style = Style::default().bg(Color::Yellow).fg(Color::Black);
                 (text, style, cursor_pos)
             }
             InputMode::Normal => {
-                let text = [Text::raw(format!(" {}", self.filename))];
-                let style = Style::default().bg(Color::White).fg(Color::Black);
+                let status = match self.computer.fault {
+                    Some(fault) => format!("error: {}", fault),
+                    None if self.is_running => "running".to_owned(),
+                    None => "paused".to_owned()
+                };
+                let mut status = format!(
+                    " {} | {} | {} instr/frame | cycle {}",
+                    self.filename,
+                    status,
+                    self.speed,
+                    self.computer.cycles
+                );
+                if let Some(message) = &self.message {
+                    status.push_str(&format!(" | {}", message));
+                }
+                let text = [Text::raw(status)];
+                let style = match self.computer.fault {
+                    Some(_) => Style::default().bg(Color::Red).fg(Color::White),
+                    None => Style::default().bg(Color::White).fg(Color::Black)
+                };
                 let cursor_pos = None;
                 (text, style, cursor_pos)
             }
@@ -223,18 +1028,93 @@ impl App {
         self.cursor_pos = cursor_pos;
 
         if self.is_full_screen {
-            f.render_widget(screen_block, rows[0]);
+            self.draw_screen(f, rows[0]);
         } else {
-            f.render_stateful_widget(rom_block, column1[0], &mut self.rom_cursor);
+            f.render_stateful_widget(rom_block, column1[0], &mut window_state(rom_selected, &rom_rows));
             f.render_widget(pc_block, column1[1]);
-            f.render_stateful_widget(ram_block, column2[0], &mut self.ram_cursor);
+            f.render_stateful_widget(ram_block, column2[0], &mut window_state(ram_selected, &ram_rows));
             f.render_widget(d_register_block, column2[1]);
             f.render_widget(a_register_block, column2[2]);
-            f.render_widget(screen_block, column3[0]);
+            self.draw_screen(f, column3[0]);
+            f.render_stateful_widget(breakpoints_block, column3[1], &mut self.breakpoint_cursor);
             f.render_widget(command_input, rows[1]);
         }
     }
 
+    /// Draws the screen, or the memory map in its place.
+    fn draw_screen<B: backend::Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        if self.is_memory_map {
+            let (text, words_per_cell) = self.memory_map(area);
+            let title = format!("[Memory: {} words/cell]", words_per_cell);
+            let memory_map_block = Paragraph::new(text.iter())
+                .block(Block::default().borders(Borders::ALL).title(&title));
+            f.render_widget(memory_map_block, area);
+        } else {
+            let dots = Points {coords: &self.get_screen_dots(), color: Color::White};
+            let screen_block = Canvas::default()
+                .block(Block::default().borders(Borders::ALL).title("[Screen]"))
+                .paint(|ctx| {
+                    ctx.draw(&dots);
+                })
+                .x_bounds([0.0, 512.0])
+                .y_bounds([0.0, 256.0]);
+            f.render_widget(screen_block, area);
+        }
+    }
+
+    /// Lays the 32K address space out in rows that fit `area`, each starting
+    /// with its first address. A cell stands for a power of two number of
+    /// words and is coloured by how often they were accessed: `█` when one
+    /// was written, `░` when they were only read and `·` when untouched.
+    fn memory_map(&self, area: Rect) -> (Vec<Text<'static>>, usize) {
+        const ADDRESS_SPACE: usize = 1 << 15;
+        const LABEL_WIDTH: usize = 6;
+        let width = (area.width as usize).saturating_sub(2 + LABEL_WIDTH).max(1);
+        let height = (area.height as usize).saturating_sub(2).max(1);
+
+        // Power of two rows keep the addresses at the start of each row round
+        let columns = 1 << (usize::BITS - 1 - width.leading_zeros());
+        let mut words_per_cell = 1;
+        while ADDRESS_SPACE / (columns * words_per_cell) > height && columns * words_per_cell < ADDRESS_SPACE {
+            words_per_cell *= 2;
+        }
+
+        let cells: Vec<Option<(u64, u64)>> = match self.computer.memory_profile() {
+            Some(counts) => (0..ADDRESS_SPACE / words_per_cell)
+                .map(|cell| {
+                    let start = cell * words_per_cell;
+                    let end = (start + words_per_cell).min(counts.reads.len());
+                    if start >= end {
+                        return None;
+                    }
+                    Some((counts.reads[start..end].iter().sum(), counts.writes[start..end].iter().sum()))
+                })
+                .collect(),
+            None => vec![None; ADDRESS_SPACE / words_per_cell]
+        };
+        let max_accesses = cells.iter().flatten().map(|(reads, writes)| reads + writes).max().unwrap_or(0);
+
+        let mut text = vec![];
+        for (row, row_cells) in cells.chunks(columns).enumerate() {
+            if row > 0 {
+                text.push(Text::raw("\n"));
+            }
+            text.push(Text::raw(format!("{:>5} ", row * columns * words_per_cell)));
+            for cell in row_cells {
+                text.push(match *cell {
+                    // Past the end of RAM
+                    None => Text::raw(" "),
+                    Some((0, 0)) => Text::raw("·"),
+                    Some((reads, writes)) => {
+                        let style = Style::default().fg(heat_color(reads + writes, max_accesses));
+                        Text::styled(if writes > 0 { "█" } else { "░" }, style)
+                    }
+                });
+            }
+        }
+        (text, words_per_cell)
+    }
+
     fn get_screen_dots(&self) -> Vec<(f64, f64)> {
         let mut coords = vec![];
         let mut n = 0;
@@ -253,3 +1133,39 @@ impl App {
         coords
     }
 }
+
+/// The rows of a list panel to build: those from `offset` on, which is moved
+/// just far enough to keep `selected` in view, the way `List` scrolls.
+fn visible_rows(offset: &mut usize, selected: Option<usize>, height: usize, len: usize) -> Range<usize> {
+    if let Some(selected) = selected {
+        if selected < *offset {
+            *offset = selected;
+        } else if selected >= *offset + height {
+            *offset = selected + 1 - height;
+        }
+    }
+    *offset = (*offset).min(len.saturating_sub(height));
+    *offset..(*offset + height).min(len)
+}
+
+/// Selects `selected` within a list built from just the `rows` of a panel.
+fn window_state(selected: Option<usize>, rows: &Range<usize>) -> ListState {
+    let mut state = ListState::default();
+    state.select(selected.filter(|selected| rows.contains(selected)).map(|selected| selected - rows.start));
+    state
+}
+
+/// Colours an execution or access count from cold to hot, on a log scale so that
+/// rarely run code or rarely touched RAM still stands out from what was never used.
+fn heat_color(count: u64, max_count: u64) -> Color {
+    if count == 0 {
+        return Color::Reset;
+    }
+    let heat = ((count + 1) as f64).ln() / ((max_count + 1) as f64).ln();
+    match heat {
+        _ if heat >= 0.75 => Color::Red,
+        _ if heat >= 0.5 => Color::Magenta,
+        _ if heat >= 0.25 => Color::Cyan,
+        _ => Color::Blue
+    }
+}
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
+use std::fmt;
 use phf::{Map, phf_map};
-use crate::utils::{get_bit, get_bit_slice};
+use crate::computer::ROM_SIZE;
+use crate::disassembler::{R_DEST_SYMBOLS, R_JUMP_SYMBOLS};
 
 static DEST_SYMBOLS: Map<&'static str, i16> = phf_map! {
     "M" =>  0b001, "D" =>   0b010, "MD" => 0b011, "A" => 0b100, "AM" => 0b101,
@@ -22,59 +24,235 @@ static JUMP_SYMBOLS: Map<&'static str, i16> = phf_map! {
     "JLE" => 0b110, "JMP" => 0b111
 };
 
-static R_DEST_SYMBOLS: [&'static str; 8] = ["", "M", "D", "MD", "A", "AM", "AD", "AMD"];
+// The comp mnemonics in the order the Hack specification lists them
+static COMP_MNEMONICS: [&str; 28] = [
+    "0", "1", "-1", "D", "A", "M", "!D", "!A", "!M", "-D", "-A", "-M", "D+1", "A+1",
+    "M+1", "D-1", "A-1", "M-1", "D+A", "D+M", "D-A", "D-M", "A-D", "M-D", "D&A", "D&M",
+    "D|A", "D|M"
+];
 
-static R_COMP_SYMBOLS: Map<i16, &'static str> = phf_map! {
-    0b0101010i16 => "0",   0b0111111i16 => "1",   0b0111010i16 => "-1",  0b0001100i16 => "D",
-    0b0110000i16 => "A",   0b1110000i16 => "M",   0b0001101i16 => "!D",  0b0110001i16 => "!A",
-    0b1110001i16 => "!M",  0b0001111i16 => "-D" , 0b0110011i16 => "-A",  0b1110011i16 => "-M",
-    0b0011111i16 => "D+1", 0b0110111i16 => "A+1", 0b1110111i16 => "M+1", 0b0001110i16 => "D-1",
-    0b0110010i16 => "A-1", 0b1110010i16 => "M-1", 0b0000010i16 => "D+A", 0b1000010i16 => "D+M",
-    0b0010011i16 => "D-A", 0b1010011i16 => "D-M", 0b0000111i16 => "A-D", 0b1000111i16 => "M-D",
-    0b0000000i16 => "D&A", 0b1000000i16 => "D&M", 0b0010101i16 => "D|A", 0b1010101i16 => "D|M"
-};
+// The largest address an A-instruction can load
+const MAX_ADDRESS: usize = 32_767;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SymbolKind {
+    Predefined,
+    Label,
+    Variable
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub value: u16,
+    pub kind: SymbolKind
+}
+
+impl Symbol {
+    fn new(name: &str, value: u16, kind: SymbolKind) -> Symbol {
+        Symbol { name: name.to_owned(), value, kind }
+    }
+}
+
+/// The output of a successful assembly.
+#[derive(Debug, Clone)]
+pub struct Assembly {
+    pub code: Vec<i16>,
+    /// The 0-based source line each instruction was assembled from
+    pub source_map: Vec<usize>,
+    /// Every symbol the program could refer to, ordered by kind and value
+    pub symbols: Vec<Symbol>
+}
+
+impl Assembly {
+    /// Lists every source line next to the address and binary code it
+    /// assembled to.
+    pub fn listing(&self, source: &[String]) -> String {
+        let mut instructions = self.source_map.iter().zip(&self.code).enumerate().peekable();
+        let mut out = String::new();
+        for (line_number, line) in source.iter().enumerate() {
+            match instructions.peek() {
+                Some((address, (&source_line, instr))) if source_line == line_number => {
+                    out.push_str(&format!("{:5}  {:016b}  {}\n", address, **instr as u16, line));
+                    instructions.next();
+                }
+                _ => out.push_str(&format!("{:5}  {:16}  {}\n", "", "", line))
+            }
+        }
+        out
+    }
+
+    pub fn symbol_dump(&self) -> String {
+        let mut out = String::new();
+        for symbol in &self.symbols {
+            let kind = match symbol.kind {
+                SymbolKind::Predefined => "predefined",
+                SymbolKind::Label => "label",
+                SymbolKind::Variable => "variable"
+            };
+            out.push_str(&format!("{:<24} {:5}  {}\n", symbol.name, symbol.value, kind));
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    InvalidDest,
+    InvalidComp,
+    InvalidJump,
+    MissingAddress,
+    AddressOutOfRange,
+    InvalidSymbol,
+    MalformedLabel,
+    DuplicateLabel,
+    /// A label or variable whose address doesn't fit in an A-instruction
+    SymbolOutOfRange,
+    ProgramTooLarge
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyError {
+    pub kind: ErrorKind,
+    /// 1-based line number in the source
+    pub line: usize,
+    /// 1-based column of the offending token
+    pub column: usize,
+    pub token: String,
+    pub expected: Vec<&'static str>
+}
+
+impl AssemblyError {
+    pub fn message(&self) -> String {
+        match self.kind {
+            ErrorKind::InvalidDest => format!("invalid dest `{}`", self.token),
+            ErrorKind::InvalidComp => format!("invalid comp `{}`", self.token),
+            ErrorKind::InvalidJump => format!("invalid jump `{}`", self.token),
+            ErrorKind::MissingAddress => "missing address after `@`".to_owned(),
+            ErrorKind::AddressOutOfRange => format!("constant `{}` is out of range", self.token),
+            ErrorKind::InvalidSymbol => format!("invalid symbol `{}`", self.token),
+            ErrorKind::MalformedLabel => format!("malformed label `{}`", self.token),
+            ErrorKind::DuplicateLabel => format!("label `{}` is already defined", self.token),
+            ErrorKind::SymbolOutOfRange => format!("the address of `{}` is out of range", self.token),
+            ErrorKind::ProgramTooLarge => format!("`{}` does not fit in the {} words of ROM", self.token, ROM_SIZE)
+        }
+    }
+
+    /// Formats the error compiler-style, quoting the offending source line.
+    pub fn render(&self, filename: &str, source: &[String]) -> String {
+        let line_number = self.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let source_line = source.get(self.line - 1).map_or("", |l| l.as_str());
+        let underline = "^".repeat(self.token.chars().count().max(1));
+        // Keep the tabs before the token so the caret lines up however wide they're shown
+        let indent: String = source_line.get(..self.column - 1).unwrap_or("")
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let mut out = format!("error: {}\n", self.message());
+        out.push_str(&format!("{}--> {}:{}:{}\n", gutter, filename, self.line, self.column));
+        out.push_str(&format!("{} |\n", gutter));
+        out.push_str(&format!("{} | {}\n", line_number, source_line));
+        out.push_str(&format!("{} | {}{}", gutter, indent, underline));
+        if !self.expected.is_empty() {
+            out.push_str(&format!("\n{} = expected one of: {}", gutter, self.expected.join(", ")));
+        }
+        out
+    }
+}
 
-static R_JUMP_SYMBOLS: [&'static str; 8] = ["", "JGT", "JEQ", "JGE", "JLT", "JNE", "JLE", "JMP"];
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message())
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+#[derive(Debug, Clone, Copy)]
+struct Span<'a> {
+    text: &'a str,
+    column: usize
+}
+
+impl<'a> Span<'a> {
+    fn error(&self, kind: ErrorKind, line_number: usize, expected: Vec<&'static str>) -> AssemblyError {
+        AssemblyError {
+            kind,
+            line: line_number + 1,
+            column: self.column,
+            token: self.text.to_owned(),
+            expected
+        }
+    }
+}
 
 #[derive(Debug)]
 enum Command<'a> {
-    A { address: &'a str, line_number: usize },
-    C { dest: Option<&'a str>, comp: &'a str, jump: Option<&'a str>, line_number: usize },
-    L { label: &'a str, line_number: usize },
+    A { address: Span<'a>, instruction: Span<'a>, line_number: usize },
+    C { dest: Option<Span<'a>>, comp: Span<'a>, jump: Option<Span<'a>>, instruction: Span<'a>, line_number: usize },
+    L { label: Span<'a>, line_number: usize },
+}
+
+impl<'a> Command<'a> {
+    fn line_number(&self) -> usize {
+        match *self {
+            Command::A { line_number, .. }
+            | Command::C { line_number, .. }
+            | Command::L { line_number, .. } => line_number
+        }
+    }
+}
+
+fn is_valid_symbol(symbol: &str) -> bool {
+    match symbol.chars().next() {
+        Some(c) if !c.is_ascii_digit() => symbol.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.$:".contains(c)),
+        _ => false
+    }
 }
 
-fn tokenize(input: &Vec<String>) -> Vec<Command> {
+fn tokenize<'a>(input: &'a [String], errors: &mut Vec<AssemblyError>) -> Vec<Command<'a>> {
     let mut commands = vec![];
 
     for (line_number, line) in input.iter().enumerate() {
-        let line = {
-            let line = line.trim_start();
-            let end = line.find('/').unwrap_or(line.len());
-            line[0..end].trim_end()
+        let (line, start) = {
+            let trimmed = line.trim_start();
+            let start = line.len() - trimmed.len() + 1;
+            let end = trimmed.find('/').unwrap_or(trimmed.len());
+            (trimmed[0..end].trim_end(), start)
         };
+        let span = |from: usize, to: usize| Span { text: &line[from..to], column: start + from };
 
         match line.chars().next() {
             Some('/') | None => continue,
-            Some('@') => commands.push(Command::A {
-                address: &line[1..line.len()],
-                line_number
-            }),
-            Some('(') => commands.push(Command::L {
-                label: &line[1..line.len()-1],
-                line_number
-            }),
+            Some('@') => {
+                if line.len() == 1 {
+                    errors.push(span(0, 1).error(ErrorKind::MissingAddress, line_number, vec![]));
+                } else {
+                    commands.push(Command::A { address: span(1, line.len()), instruction: span(0, line.len()), line_number });
+                }
+            }
+            Some('(') => {
+                if line.len() < 3 || !line.ends_with(')') {
+                    errors.push(span(0, line.len()).error(ErrorKind::MalformedLabel, line_number, vec!["(LABEL)"]));
+                } else {
+                    commands.push(Command::L { label: span(1, line.len() - 1), line_number });
+                }
+            }
             Some(_) => {
-                let eq_sep = line[0..line.len()].find('=');
-                let colon_sep = line[0..line.len()].find(';');
+                let eq_sep = line.find('=');
+                let colon_sep = line.find(';');
 
-                let dest = eq_sep.map(|end| &line[0..end]);
+                let dest = eq_sep.map(|end| span(0, end));
                 let comp = {
                     let start = eq_sep.map(|start| start + 1).unwrap_or(0);
-                    let end = colon_sep.unwrap_or(line.len());
-                    &line[start..end]
+                    let end = colon_sep.unwrap_or(line.len()).max(start);
+                    span(start, end)
                 };
-                let jump = colon_sep.map(|start| &line[start + 1..line.len()]);
-                commands.push(Command::C { dest, comp, jump, line_number })
+                let jump = colon_sep.map(|start| span(start + 1, line.len()));
+                commands.push(Command::C { dest, comp, jump, instruction: span(0, line.len()), line_number })
             }
         };
     }
@@ -82,118 +260,145 @@ fn tokenize(input: &Vec<String>) -> Vec<Command> {
     commands
 }
 
-fn transform(commands: &Vec<Command>, symbol_table: &mut HashMap<String, u32>) -> Vec<i16> {
-    let mut current_line = 0;
+fn comp_bits(comp: &str) -> Option<i16> {
+    match COMP_SYMBOLS.get(comp) {
+        Some(v) => Some(*v),
+        None => {
+            // Binary operators other than `-` are commutative, e.g. `A+D` is `D+A`
+            let rcomp: Vec<char> = comp.chars().rev().collect();
+            if rcomp.len() != 3 || rcomp[1] == '-' { return None; }
+            let rcomp: String = rcomp.iter().collect();
+            COMP_SYMBOLS.get(&rcomp as &str).copied()
+        }
+    }
+}
+
+fn transform(
+    commands: &[Command],
+    symbol_table: &mut HashMap<String, Symbol>,
+    errors: &mut Vec<AssemblyError>
+) -> (Vec<i16>, Vec<usize>) {
+    let mut current_line: usize = 0;
 
     for command in commands.iter() {
         match *command {
-            Command::L { label, .. } => {
-                symbol_table.insert(label.to_owned(), current_line);
+            Command::L { label, line_number } => {
+                if !is_valid_symbol(label.text) {
+                    errors.push(label.error(ErrorKind::InvalidSymbol, line_number, vec![]));
+                } else if symbol_table.contains_key(label.text) {
+                    errors.push(label.error(ErrorKind::DuplicateLabel, line_number, vec![]));
+                } else if current_line > MAX_ADDRESS {
+                    errors.push(label.error(ErrorKind::SymbolOutOfRange, line_number, vec!["0..=32767"]));
+                } else {
+                    let symbol = Symbol::new(label.text, current_line as u16, SymbolKind::Label);
+                    symbol_table.insert(label.text.to_owned(), symbol);
+                }
             }
-            _ => {
+            Command::A { instruction, line_number, .. } | Command::C { instruction, line_number, .. } => {
+                // Only the first instruction past the end is reported
+                if current_line == ROM_SIZE {
+                    errors.push(instruction.error(ErrorKind::ProgramTooLarge, line_number, vec![]));
+                }
                 current_line += 1;
             }
         }
     }
-    
-    let mut memory_address = 15;
+
+    let mut memory_address: usize = 15;
     let mut binary_code = vec![];
+    let mut source_map = vec![];
 
     for command in commands.iter() {
         match command {
-            Command::A { address, .. } => {
-                match address.parse::<i16>() {
-                    Ok(number) => {
-                        binary_code.push(number);
+            Command::A { address, line_number, .. } => {
+                if address.text.starts_with(|c: char| c.is_ascii_digit()) {
+                    match address.text.parse::<i16>() {
+                        Ok(number) if number >= 0 => binary_code.push(number),
+                        _ => {
+                            let error = if address.text.chars().all(|c| c.is_ascii_digit()) {
+                                address.error(ErrorKind::AddressOutOfRange, *line_number, vec!["0..=32767"])
+                            } else {
+                                address.error(ErrorKind::InvalidSymbol, *line_number, vec![])
+                            };
+                            errors.push(error);
+                        }
                     }
-                    Err(_) => {
-                        let number = symbol_table.entry(address.to_string())
-                            .or_insert_with(|| {
-                                memory_address += 1;
-                                memory_address
-                            });
-                        binary_code.push(*number as i16);
+                } else if is_valid_symbol(address.text) {
+                    match symbol_table.get(address.text) {
+                        Some(symbol) => binary_code.push(symbol.value as i16),
+                        None if memory_address < MAX_ADDRESS => {
+                            memory_address += 1;
+                            let symbol = Symbol::new(address.text, memory_address as u16, SymbolKind::Variable);
+                            symbol_table.insert(address.text.to_owned(), symbol);
+                            binary_code.push(memory_address as i16);
+                        }
+                        None => errors.push(address.error(ErrorKind::SymbolOutOfRange, *line_number, vec!["0..=32767"]))
                     }
-                };
+                } else {
+                    errors.push(address.error(ErrorKind::InvalidSymbol, *line_number, vec![]));
+                }
             }
-            Command::C { dest, comp, jump, line_number } => {
-                let c_bits = match COMP_SYMBOLS.get(*comp) {
-                    Some(v) => *v,
-                    None => {
-                        let error_message = format!("invalid comp on line {}: {}", line_number + 1, comp);
-                        let rcomp: Vec<char> = comp.chars().rev().collect();
-                        if rcomp.len() != 3 || rcomp[1] == '-' { panic!(error_message); }
-                        let rcomp: String = rcomp.iter().collect();
-                        *COMP_SYMBOLS.get(&rcomp as &str).expect(&error_message)
-                    }
-                };
-
-                let d_bits = dest.map_or(0b000, |dest|
-                    *DEST_SYMBOLS.get(dest).expect(&format!(
-                        "invalid dest on line {}: {}",
-                        line_number + 1,
-                        dest
-                    ))
-                );
-                let j_bits = jump.map_or(0b000, |jump|
-                    *JUMP_SYMBOLS.get(jump).expect(&format!(
-                        "invalid jump on line {}: {}",
-                        line_number + 1,
-                        jump
-                    ))
-                );
+            Command::C { dest, comp, jump, line_number, .. } => {
+                let c_bits = comp_bits(comp.text).unwrap_or_else(|| {
+                    errors.push(comp.error(ErrorKind::InvalidComp, *line_number, COMP_MNEMONICS.to_vec()));
+                    0
+                });
+                let d_bits = dest.map_or(0b000, |dest| {
+                    DEST_SYMBOLS.get(dest.text).copied().unwrap_or_else(|| {
+                        errors.push(dest.error(ErrorKind::InvalidDest, *line_number, R_DEST_SYMBOLS[1..].to_vec()));
+                        0
+                    })
+                });
+                let j_bits = jump.map_or(0b000, |jump| {
+                    JUMP_SYMBOLS.get(jump.text).copied().unwrap_or_else(|| {
+                        errors.push(jump.error(ErrorKind::InvalidJump, *line_number, R_JUMP_SYMBOLS[1..].to_vec()));
+                        0
+                    })
+                });
                 let b = (0b111 << 13) + (c_bits << 6) + (d_bits << 3) + (j_bits);
                 binary_code.push(b);
             }
-            _ => {}
+            Command::L { .. } => continue
         };
+        source_map.push(command.line_number());
     }
 
-    binary_code
+    (binary_code, source_map)
 }
 
-fn init_symbol_table() -> HashMap<String, u32> {
-    let mut symbol_table = HashMap::new();
-    symbol_table.insert(String::from("SP"), 0);
-    symbol_table.insert(String::from("LCL"), 1);
-    symbol_table.insert(String::from("ARG"), 2);
-    symbol_table.insert(String::from("THIS"), 3);
-    symbol_table.insert(String::from("THAT"), 4);
-    symbol_table.insert(String::from("SCREEN"), 16384);
-    symbol_table.insert(String::from("KBD"), 24576);
-    (0..16).for_each(|i| {
-        symbol_table.insert(format!("R{}", i), i);
-    });
+/// The symbols every program can refer to without defining them.
+pub fn predefined_symbols() -> Vec<Symbol> {
+    let mut symbols = vec![
+        ("SP", 0), ("LCL", 1), ("ARG", 2), ("THIS", 3), ("THAT", 4),
+        ("SCREEN", 16384), ("KBD", 24576)
+    ];
+    let registers: Vec<String> = (0..16).map(|i| format!("R{}", i)).collect();
+    symbols.extend(registers.iter().enumerate().map(|(i, name)| (name.as_str(), i as u16)));
 
-    symbol_table
+    symbols.into_iter()
+        .map(|(name, value)| Symbol::new(name, value, SymbolKind::Predefined))
+        .collect()
 }
 
-pub fn assemble(input: &Vec<String>) -> Vec<i16> {
-    let mut symbol_table = init_symbol_table();
-    let commands = tokenize(&input);
-    let binary_code = transform(&commands, &mut symbol_table);
-    binary_code
+fn init_symbol_table() -> HashMap<String, Symbol> {
+    predefined_symbols().into_iter()
+        .map(|symbol| (symbol.name.clone(), symbol))
+        .collect()
 }
 
-pub fn to_asm(instr: i16) -> String {
-    let is_a_instr = !get_bit(instr, 15);
-    if is_a_instr {
-        format!("@{}", instr)
-    }
-    else {
-        let comp_bits = get_bit_slice(instr, 6, 13);
-        let dest_bits = get_bit_slice(instr, 3, 6);
-        let jump_bits = get_bit_slice(instr, 0, 3);
-        let mut asm = "".to_owned();
-
-        if dest_bits != 0 {
-            asm.push_str(&format!("{}=", R_DEST_SYMBOLS[dest_bits as usize]));
-        }
-        asm.push_str(R_COMP_SYMBOLS.get(&comp_bits).unwrap());
-        if jump_bits != 0 {
-            asm.push_str(&format!(";{}", R_JUMP_SYMBOLS[jump_bits as usize]));
-        }
-        
-        asm
+/// Assembles the given source lines, collecting every error rather than
+/// stopping at the first one.
+pub fn assemble(input: &[String]) -> Result<Assembly, Vec<AssemblyError>> {
+    let mut symbol_table = init_symbol_table();
+    let mut errors = vec![];
+    let commands = tokenize(input, &mut errors);
+    let (code, source_map) = transform(&commands, &mut symbol_table, &mut errors);
+    if errors.is_empty() {
+        let mut symbols: Vec<Symbol> = symbol_table.into_values().collect();
+        symbols.sort_by(|a, b| (a.kind, a.value, &a.name).cmp(&(b.kind, b.value, &b.name)));
+        Ok(Assembly { code, source_map, symbols })
+    } else {
+        errors.sort_by_key(|e| (e.line, e.column));
+        Err(errors)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,371 @@
+use std::ffi::OsStr;
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use hack_cpu_emulator::computer::DEFAULT_HISTORY_SIZE;
+use hack_cpu_emulator::disassembler::Syntax;
+use hack_cpu_emulator::format::NumberFormat;
+use hack_cpu_emulator::headless::{self, Dump, DEFAULT_MAX_CYCLES};
+use hack_cpu_emulator::trace::TraceFormat;
+
+/// Instructions the terminal UI executes per frame while running
+pub const DEFAULT_SPEED: usize = 1 << 10;
+const DEFAULT_CONTEXT: usize = 5;
+
+pub const USAGE: &str = "\
+Usage: hack-cpu-emulator [command] [options] <file>
+
+Commands:
+    tui            Debug a program in the terminal UI (default)
+    run            Run a program without the UI and print registers and memory
+    assemble       Assemble an .asm file into a .hack file
+    disassemble    Print the assembly of an .asm or .hack file
+    test           Run a CPUEmulator .tst test script
+    trace          Print a binary execution trace as text
+    diff           Run two programs in lockstep and show where they first differ
+
+<file> can be an .asm file or a pre-assembled .hack file. Without a command,
+.tst files are run as test scripts and anything else is opened in the UI.
+
+Options:
+    -h, --help                 Print this message
+    -V, --version              Print the version
+
+tui:
+    --history <n>              Instructions remembered for stepping back [default: 1000000]
+    --speed <n>                Instructions executed per frame while running [default: 1024]
+    --trace <file>             Write every executed instruction to a trace file
+    --trace-format <text|bin>  Format of the trace file [default: text]
+
+run:
+    --max-cycles <n>           Stop after this many instructions [default: 10000000]
+    --ram-dump <list>          Registers and memory to print, e.g. A,D,PC,RAM[0],RAM[256..260]
+                               [default: A,D,PC,RAM[0..16]]
+    --format <format>          Number format of the printed values: dec, udec (unsigned),
+                               hex or bin [default: dec]
+    --trace <file>             Write every executed instruction to a trace file
+    --trace-format <text|bin>  Format of the trace file [default: text]
+    --profile                  Also print the cycles spent per label and the most executed
+                               instructions
+    Exits with 3 on an invalid memory access, 4 when the PC leaves ROM and 5 when
+    it reaches an empty ROM cell.
+
+assemble:
+    -o, --output <file>        Output file [default: <file>.hack]
+    --listing                  Also write a <file>.lst listing
+    --symbols                  Also write the symbol table to <file>.sym
+
+disassemble:
+    -o, --output <file>        Output file [default: stdout]
+    --format <format>          Annotate each instruction with its address and binary code
+                               in the given number format
+    --control-bits             Show the ALU control bits that are set instead of comp mnemonics
+
+diff <left> <right>:
+    --max-cycles <n>           Stop after this many instructions [default: 10000000]
+    --context <n>              Instructions shown before the difference [default: 5]
+    Either file can also be a binary trace. Exits with 1 when they differ.";
+
+// Only read by the terminal UI
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+pub struct TuiOptions {
+    pub path: PathBuf,
+    pub history_size: usize,
+    pub speed: usize,
+    pub trace: Option<TraceOptions>
+}
+
+pub struct RunOptions {
+    pub path: PathBuf,
+    pub max_cycles: u64,
+    pub dumps: Vec<Dump>,
+    pub format: NumberFormat,
+    pub trace: Option<TraceOptions>,
+    pub profile: bool
+}
+
+pub struct TraceOptions {
+    pub path: PathBuf,
+    pub format: TraceFormat
+}
+
+pub struct DiffOptions {
+    pub left: PathBuf,
+    pub right: PathBuf,
+    pub max_cycles: u64,
+    pub context: usize
+}
+
+pub struct AssembleOptions {
+    pub path: PathBuf,
+    pub output: Option<PathBuf>,
+    pub listing: bool,
+    pub symbols: bool
+}
+
+pub struct DisassembleOptions {
+    pub path: PathBuf,
+    pub output: Option<PathBuf>,
+    pub format: Option<NumberFormat>,
+    pub syntax: Syntax
+}
+
+pub enum Command {
+    Help,
+    Version,
+    Tui(TuiOptions),
+    Run(RunOptions),
+    Assemble(AssembleOptions),
+    Disassemble(DisassembleOptions),
+    Test(PathBuf),
+    Trace(PathBuf),
+    Diff(DiffOptions)
+}
+
+struct Args<I: Iterator<Item = String>> {
+    args: Peekable<I>,
+    paths: Vec<PathBuf>,
+    max_paths: usize
+}
+
+impl<I: Iterator<Item = String>> Args<I> {
+    fn value<T: FromStr>(&mut self, flag: &str) -> Result<T, String> {
+        let value = self.args.next().ok_or_else(|| format!("{} expects a value", flag))?;
+        value.parse().map_err(|_| format!("invalid value for {}: {}", flag, value))
+    }
+
+    fn positional(&mut self, arg: String) -> Result<(), String> {
+        if arg.starts_with('-') && arg.len() > 1 {
+            return Err(format!("unexpected option: {}", arg));
+        }
+        if self.paths.len() == self.max_paths {
+            return Err(format!("unexpected argument: {}", arg));
+        }
+        self.paths.push(PathBuf::from(arg));
+        Ok(())
+    }
+
+    fn path(&mut self) -> Result<PathBuf, String> {
+        match self.paths.len() {
+            0 => Err("missing <file> argument".to_owned()),
+            _ => Ok(self.paths.remove(0))
+        }
+    }
+}
+
+pub fn parse_args(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut args = Args { args: args.peekable(), paths: vec![], max_paths: 1 };
+    let command = match args.args.peek().map(String::as_str) {
+        Some(name @ "tui") | Some(name @ "run") | Some(name @ "assemble") | Some(name @ "disassemble")
+        | Some(name @ "test") | Some(name @ "trace") | Some(name @ "diff") => {
+            let name = name.to_owned();
+            args.args.next();
+            Some(name)
+        }
+        _ => None
+    };
+    if command.as_deref() == Some("diff") {
+        args.max_paths = 2;
+    }
+
+    if args.args.peek().is_none() && command.is_none() {
+        return Ok(Command::Help);
+    }
+
+    let mut history_size = DEFAULT_HISTORY_SIZE;
+    let mut speed = DEFAULT_SPEED;
+    let mut max_cycles = DEFAULT_MAX_CYCLES;
+    let mut dumps = headless::default_dumps();
+    let mut format = None;
+    let mut output = None;
+    let mut listing = false;
+    let mut symbols = false;
+    let mut syntax = Syntax::Mnemonic;
+    let mut trace_path: Option<PathBuf> = None;
+    let mut trace_format = TraceFormat::Text;
+    let mut context = DEFAULT_CONTEXT;
+    let mut profile = false;
+
+    let command = command.as_deref();
+    while let Some(arg) = args.args.next() {
+        match (command, arg.as_str()) {
+            (_, "-h") | (_, "--help") => return Ok(Command::Help),
+            (_, "-V") | (_, "--version") => return Ok(Command::Version),
+            (None, "--history") | (Some("tui"), "--history") => history_size = args.value(&arg)?,
+            (None, "--speed") | (Some("tui"), "--speed") => speed = args.value(&arg)?,
+            (None, "--trace") | (Some("tui"), "--trace") | (Some("run"), "--trace") => {
+                trace_path = Some(args.value(&arg)?);
+            }
+            (None, "--trace-format") | (Some("tui"), "--trace-format") | (Some("run"), "--trace-format") => {
+                trace_format = args.value(&arg)?;
+            }
+            (Some("run"), "--max-cycles") | (Some("diff"), "--max-cycles") => max_cycles = args.value(&arg)?,
+            (Some("diff"), "--context") => context = args.value(&arg)?,
+            (Some("run"), "--profile") => profile = true,
+            (Some("run"), "--ram-dump") => {
+                let spec: String = args.value(&arg)?;
+                dumps = headless::parse_dumps(&spec)?;
+            }
+            (Some("run"), "--format") | (Some("disassemble"), "--format") => {
+                format = Some(args.value(&arg)?);
+            }
+            (Some("assemble"), "-o") | (Some("assemble"), "--output")
+            | (Some("disassemble"), "-o") | (Some("disassemble"), "--output") => {
+                output = Some(args.value(&arg)?);
+            }
+            (Some("assemble"), "--listing") => listing = true,
+            (Some("assemble"), "--symbols") => symbols = true,
+            (Some("disassemble"), "--control-bits") => syntax = Syntax::ControlBits,
+            _ => args.positional(arg)?
+        }
+    }
+
+    let path = args.path()?;
+    let trace = trace_path.map(|path| TraceOptions { path, format: trace_format });
+    Ok(match command {
+        Some("run") => Command::Run(RunOptions {
+            path,
+            max_cycles,
+            dumps,
+            format: format.unwrap_or(NumberFormat::Decimal),
+            trace,
+            profile
+        }),
+        Some("assemble") => Command::Assemble(AssembleOptions { path, output, listing, symbols }),
+        Some("disassemble") => Command::Disassemble(DisassembleOptions { path, output, format, syntax }),
+        Some("test") => Command::Test(path),
+        Some("trace") => Command::Trace(path),
+        Some("diff") => {
+            let right = args.path().map_err(|_| "diff expects two files".to_owned())?;
+            Command::Diff(DiffOptions { left: path, right, max_cycles, context })
+        }
+        None if path.extension() == Some(OsStr::new("tst")) => Command::Test(path),
+        _ => Command::Tui(TuiOptions { path, history_size, speed, trace })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn error(args: &[&str]) -> String {
+        match parse(args) {
+            Err(error) => error,
+            Ok(_) => panic!("{:?} parsed", args)
+        }
+    }
+
+    #[test]
+    fn help_and_version() {
+        assert!(matches!(parse(&[]), Ok(Command::Help)));
+        assert!(matches!(parse(&["run", "--help"]), Ok(Command::Help)));
+        assert!(matches!(parse(&["-V"]), Ok(Command::Version)));
+    }
+
+    #[test]
+    fn tui_defaults() {
+        for args in [&["Prog.asm"][..], &["tui", "Prog.asm"]] {
+            match parse(args) {
+                Ok(Command::Tui(options)) => {
+                    assert_eq!(options.path, PathBuf::from("Prog.asm"));
+                    assert_eq!(options.history_size, DEFAULT_HISTORY_SIZE);
+                    assert_eq!(options.speed, DEFAULT_SPEED);
+                    assert!(options.trace.is_none());
+                }
+                _ => panic!("{:?} isn't tui", args)
+            }
+        }
+    }
+
+    #[test]
+    fn run_defaults() {
+        match parse(&["run", "Prog.hack"]) {
+            Ok(Command::Run(options)) => {
+                assert_eq!(options.path, PathBuf::from("Prog.hack"));
+                assert_eq!(options.max_cycles, DEFAULT_MAX_CYCLES);
+                assert_eq!(options.dumps.len(), headless::default_dumps().len());
+                assert_eq!(options.format, NumberFormat::Decimal);
+                assert!(options.trace.is_none());
+                assert!(!options.profile);
+            }
+            _ => panic!("not run")
+        }
+        match parse(&["run", "--format", "hex", "--max-cycles", "10", "Prog.hack"]) {
+            Ok(Command::Run(options)) => {
+                assert_eq!(options.format, NumberFormat::Hex);
+                assert_eq!(options.max_cycles, 10);
+            }
+            _ => panic!("not run")
+        }
+        match parse(&["run", "--trace", "t.bin", "--trace-format", "bin", "Prog.hack"]) {
+            Ok(Command::Run(options)) => {
+                let trace = options.trace.unwrap();
+                assert_eq!((trace.path, trace.format), (PathBuf::from("t.bin"), TraceFormat::Binary));
+            }
+            _ => panic!("not run")
+        }
+    }
+
+    #[test]
+    fn assemble_and_disassemble_defaults() {
+        match parse(&["assemble", "Prog.asm"]) {
+            Ok(Command::Assemble(options)) => {
+                assert_eq!(options.path, PathBuf::from("Prog.asm"));
+                assert!(options.output.is_none() && !options.listing && !options.symbols);
+            }
+            _ => panic!("not assemble")
+        }
+        match parse(&["disassemble", "Prog.hack"]) {
+            Ok(Command::Disassemble(options)) => {
+                assert_eq!(options.path, PathBuf::from("Prog.hack"));
+                assert!(options.output.is_none() && options.format.is_none());
+                assert_eq!(options.syntax, Syntax::Mnemonic);
+            }
+            _ => panic!("not disassemble")
+        }
+    }
+
+    #[test]
+    fn test_trace_and_diff_defaults() {
+        assert!(matches!(parse(&["test", "Mult.tst"]), Ok(Command::Test(path)) if path == Path::new("Mult.tst")));
+        // Test scripts run without a command too
+        assert!(matches!(parse(&["Mult.tst"]), Ok(Command::Test(_))));
+        assert!(matches!(parse(&["trace", "t.bin"]), Ok(Command::Trace(path)) if path == Path::new("t.bin")));
+        match parse(&["diff", "A.asm", "B.hack"]) {
+            Ok(Command::Diff(options)) => {
+                assert_eq!((options.left, options.right), (PathBuf::from("A.asm"), PathBuf::from("B.hack")));
+                assert_eq!((options.max_cycles, options.context), (DEFAULT_MAX_CYCLES, DEFAULT_CONTEXT));
+            }
+            _ => panic!("not diff")
+        }
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert_eq!(error(&["run", "--fast", "Prog.asm"]), "unexpected option: --fast");
+        assert_eq!(error(&["run", "Prog.asm", "--max-cycles"]), "--max-cycles expects a value");
+        assert_eq!(error(&["run", "--max-cycles", "many", "Prog.asm"]), "invalid value for --max-cycles: many");
+        assert_eq!(error(&["run"]), "missing <file> argument");
+        assert_eq!(error(&["run", "A.asm", "B.asm"]), "unexpected argument: B.asm");
+        assert_eq!(error(&["diff", "A.asm"]), "diff expects two files");
+        assert!(error(&["run", "--ram-dump", "X", "Prog.asm"]).contains("invalid dump"));
+    }
+
+    #[test]
+    fn options_of_other_commands() {
+        assert_eq!(error(&["assemble", "--max-cycles", "5", "Prog.asm"]), "unexpected option: --max-cycles");
+        assert_eq!(error(&["run", "--listing", "Prog.asm"]), "unexpected option: --listing");
+        assert_eq!(error(&["--profile", "Prog.asm"]), "unexpected option: --profile");
+        assert_eq!(error(&["tui", "--format", "hex", "Prog.asm"]), "unexpected option: --format");
+        assert_eq!(error(&["assemble", "--control-bits", "Prog.asm"]), "unexpected option: --control-bits");
+        assert_eq!(error(&["test", "--speed", "5", "Mult.tst"]), "unexpected option: --speed");
+        assert_eq!(error(&["test", "--trace", "t.txt", "Mult.tst"]), "unexpected option: --trace");
+    }
+}
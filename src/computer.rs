@@ -1,14 +1,154 @@
+use std::collections::VecDeque;
+use std::fmt;
+
 use crate::utils::{get_bit, get_bit_slice};
 
 pub const KBD_ADDRESS: usize = 24_576;
 pub const SCR_ADDRESS: usize = 16_384;
+pub const ROM_SIZE: usize = 32_768;
+/// RAM is addressed up to the keyboard register; the rest of the 32K
+/// address space is undefined on the Hack platform.
+pub const RAM_SIZE: usize = KBD_ADDRESS + 1;
+pub const DEFAULT_HISTORY_SIZE: usize = 1_000_000;
+
+/// Why an instruction could not be executed. The computer is left unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// `M` referred to an address outside of RAM
+    InvalidAddress(u16),
+    /// The PC points outside of ROM
+    PcOutOfBounds(u16),
+    /// The PC points at a ROM cell no program was loaded into
+    EmptyRom(u16)
+}
+
+impl Fault {
+    /// The exit status the binary uses when a headless run stops on this fault.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Fault::InvalidAddress(_) => 3,
+            Fault::PcOutOfBounds(_) => 4,
+            Fault::EmptyRom(_) => 5
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::InvalidAddress(address) => write!(f, "invalid memory access at address {}", address),
+            Fault::PcOutOfBounds(pc) => write!(f, "PC out of bounds: {}", pc),
+            Fault::EmptyRom(pc) => write!(f, "executing empty ROM at address {}", pc)
+        }
+    }
+}
+
+impl std::error::Error for Fault {}
+
+/// A program with more instructions than ROM has words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramTooLarge {
+    pub instructions: usize
+}
+
+impl fmt::Display for ProgramTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "program of {} instructions does not fit in the {} words of ROM", self.instructions, ROM_SIZE)
+    }
+}
+
+impl std::error::Error for ProgramTooLarge {}
+
+/// The Hack ALU. `comp_bits` are the six control bits `zx nx zy ny f no`
+/// of a C-instruction (without the `a` bit), applied in that order:
+/// zero and/or negate each input, add (wrapping, like the 16-bit adder)
+/// or and them, then optionally negate the output.
+/// Returns the output along with the `zr` and `ng` flags.
+pub fn alu(x: i16, y: i16, comp_bits: i16) -> (i16, bool, bool) {
+    let mut x = x;
+    let mut y = y;
+    let mut out;
+
+    let zx = get_bit(comp_bits, 5);
+    let nx = get_bit(comp_bits, 4);
+    let zy = get_bit(comp_bits, 3);
+    let ny = get_bit(comp_bits, 2);
+    let f = get_bit(comp_bits, 1);
+    let no = get_bit(comp_bits, 0);
+
+    if zx { x = 0; }
+    if nx { x = !x; }
+    if zy { y = 0; }
+    if ny { y = !y; }
+    out = if f { x.wrapping_add(y) } else { x & y };
+    if no { out = !out };
+
+    let zr = out == 0;
+    let ng = out < 0;
+
+    (out, zr, ng)
+}
+
+/// The RAM accesses made by a single instruction, as addresses and values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryAccess {
+    /// `M` used as the ALU operand
+    pub read: Option<(u16, i16)>,
+    /// `M` used as a destination, with the value written
+    pub write: Option<(u16, i16)>
+}
+
+/// How many times each RAM address was read and written through `M`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryProfile {
+    pub reads: Vec<u64>,
+    pub writes: Vec<u64>
+}
+
+impl MemoryProfile {
+    fn count(&mut self, access: MemoryAccess, undo: bool) {
+        let counts = [(access.read, &mut self.reads), (access.write, &mut self.writes)];
+        for (access, counts) in counts {
+            if let Some((address, _)) = access {
+                let count = &mut counts[address as usize];
+                *count = if undo { count.saturating_sub(1) } else { *count + 1 };
+            }
+        }
+    }
+}
+
+// Everything needed to undo a single instruction
+struct Snapshot {
+    d_register: i16,
+    a_register: i16,
+    pc: i16,
+    memory_write: Option<(u16, i16)>
+}
 
 pub struct Computer {
     pub d_register: i16,
     pub a_register: i16,
     pub pc: i16,
-    pub rom: [Option<i16>; 1000],
-    pub memory: [i16; 24_577]
+    pub rom: Box<[Option<i16>; ROM_SIZE]>,
+    pub memory: Box<[i16; RAM_SIZE]>,
+    pub cycles: u64,
+    /// Set when an instruction faults. `step` refuses to run until it is
+    /// cleared, either directly or by stepping back.
+    pub fault: Option<Fault>,
+    /// Treat executing an empty ROM cell as a fault rather than as `@0`,
+    /// which is what the official CPU emulator does.
+    pub trap_empty_rom: bool,
+    history: VecDeque<Snapshot>,
+    history_size: usize,
+    /// How many times each ROM address was executed, when profiling
+    profile: Option<Vec<u64>>,
+    memory_profile: Option<MemoryProfile>
+}
+
+impl Default for Computer {
+    fn default() -> Computer {
+        Computer::new()
+    }
 }
 
 impl Computer {
@@ -17,66 +157,192 @@ impl Computer {
             d_register: 0,
             a_register: 0,
             pc: 0,
-            rom: [None; 1000],
-            memory: [0; 24_577]
+            rom: Box::new([None; ROM_SIZE]),
+            memory: Box::new([0; RAM_SIZE]),
+            cycles: 0,
+            fault: None,
+            trap_empty_rom: true,
+            history: VecDeque::new(),
+            history_size: DEFAULT_HISTORY_SIZE,
+            profile: None,
+            memory_profile: None
         }
     }
 
-    fn alu(&self, x: i16, y: i16, comp_bits: i16) -> (i16, bool, bool) {
-        let mut x = x;
-        let mut y = y;
-        let mut out;
+    /// Copies a program to the start of ROM, leaving ROM unchanged if it doesn't fit.
+    pub fn load(&mut self, program: &[i16]) -> Result<(), ProgramTooLarge> {
+        if program.len() > ROM_SIZE {
+            return Err(ProgramTooLarge { instructions: program.len() });
+        }
+        for (i, instr) in program.iter().enumerate() {
+            self.rom[i] = Some(*instr);
+        }
+        Ok(())
+    }
+
+    pub fn set_history_size(&mut self, size: usize) {
+        self.history_size = size;
+        while self.history.len() > size {
+            self.history.pop_front();
+        }
+    }
+
+    /// Starts or stops counting how many times each ROM address is executed
+    /// and each RAM address is accessed. Stopping discards the counts.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        if !enabled {
+            self.profile = None;
+            self.memory_profile = None;
+        } else if self.profile.is_none() {
+            self.profile = Some(vec![0; ROM_SIZE]);
+            self.memory_profile = Some(MemoryProfile { reads: vec![0; RAM_SIZE], writes: vec![0; RAM_SIZE] });
+        }
+    }
 
-        let zx = get_bit(comp_bits, 5);
-        let nx = get_bit(comp_bits, 4);
-        let zy = get_bit(comp_bits, 3);
-        let ny = get_bit(comp_bits, 2);
-        let f = get_bit(comp_bits, 1);
-        let no = get_bit(comp_bits, 0);
+    /// The execution count of every ROM address, when profiling. Every
+    /// instruction takes one cycle, so these are also the cycles spent.
+    pub fn profile(&self) -> Option<&[u64]> {
+        self.profile.as_deref()
+    }
+
+    /// The RAM reads and writes made by every address, when profiling.
+    pub fn memory_profile(&self) -> Option<&MemoryProfile> {
+        self.memory_profile.as_ref()
+    }
 
+    /// The earliest cycle that can still be reached with `step_back`.
+    pub fn oldest_cycle(&self) -> u64 {
+        self.cycles - self.history.len() as u64
+    }
 
-        if zx { x = 0; }
-        if nx { x = !x; }
-        if zy { y = 0; }
-        if ny { y = !y; }
-        out = if f { x + y } else { x & y };
-        if no { out = !out };
+    /// Undoes the last instruction and clears any fault. Returns false if
+    /// there is no history left to undo.
+    pub fn step_back(&mut self) -> bool {
+        match self.history.pop_back() {
+            Some(snapshot) => {
+                self.fault = None;
+                if let Some((address, value)) = snapshot.memory_write {
+                    self.memory[address as usize] = value;
+                }
+                self.d_register = snapshot.d_register;
+                self.a_register = snapshot.a_register;
+                self.pc = snapshot.pc;
+                self.cycles -= 1;
+                if let Some(profile) = &mut self.profile {
+                    let count = &mut profile[snapshot.pc as u16 as usize];
+                    *count = count.saturating_sub(1);
+                }
+                if let Some(memory_profile) = &mut self.memory_profile {
+                    // Snapshots don't keep reads, so work out whether the instruction made one
+                    let instr = self.rom.get(self.pc as u16 as usize).copied().flatten().unwrap_or(0);
+                    let read = if get_bit(instr, 15) && get_bit(instr, 12) {
+                        Some((self.a_register as u16, 0))
+                    } else {
+                        None
+                    };
+                    let write = snapshot.memory_write.map(|(address, _)| (address, 0));
+                    memory_profile.count(MemoryAccess { read, write }, true);
+                }
+                true
+            }
+            None => false
+        }
+    }
 
-        let zr = out == 0;
-        let ng = out < 0;
+    /// Steps backwards until `cycle` is reached. Returns false without
+    /// changing anything if `cycle` is in the future or no longer in history.
+    pub fn rewind_to(&mut self, cycle: u64) -> bool {
+        if cycle > self.cycles || cycle < self.oldest_cycle() {
+            return false;
+        }
+        while self.cycles > cycle {
+            self.step_back();
+        }
+        true
+    }
 
-        (out, zr, ng)
+    fn memory_index(address: i16) -> Result<usize, Fault> {
+        let address = address as u16;
+        if (address as usize) < RAM_SIZE {
+            Ok(address as usize)
+        } else {
+            Err(Fault::InvalidAddress(address))
+        }
     }
 
-    pub fn step(&mut self) {
-        let instr = self.rom[self.pc as usize].unwrap_or(0);
+    /// Executes one instruction and reports the memory it accessed.
+    pub fn step(&mut self) -> Result<MemoryAccess, Fault> {
+        if let Some(fault) = self.fault {
+            return Err(fault);
+        }
+        let pc = self.pc as u16 as usize;
+        let result = self.execute();
+        match result {
+            Ok(access) => {
+                if let Some(profile) = &mut self.profile {
+                    profile[pc] += 1;
+                }
+                if let Some(memory_profile) = &mut self.memory_profile {
+                    memory_profile.count(access, false);
+                }
+            }
+            Err(fault) => self.fault = Some(fault)
+        }
+        result
+    }
+
+    fn execute(&mut self) -> Result<MemoryAccess, Fault> {
+        let pc = self.pc as u16 as usize;
+        let instr = match self.rom.get(pc) {
+            Some(Some(instr)) => *instr,
+            Some(None) if self.trap_empty_rom => return Err(Fault::EmptyRom(pc as u16)),
+            Some(None) => 0,
+            None => return Err(Fault::PcOutOfBounds(pc as u16))
+        };
         let is_a_instr = !get_bit(instr, 15);
         let a_bit = get_bit(instr, 12);
         let comp_bits = get_bit_slice(instr, 6, 12);
         let dest_bits = get_bit_slice(instr, 3, 6);
         let jump_bits = get_bit_slice(instr, 0, 3);
 
+        let mut access = MemoryAccess::default();
+        let mut snapshot = Snapshot {
+            d_register: self.d_register,
+            a_register: self.a_register,
+            pc: self.pc,
+            memory_write: None
+        };
+
         if is_a_instr {
             self.a_register = instr;
-            self.pc += 1;
+            self.pc = self.pc.wrapping_add(1);
         } else {
+            // M always refers to the A register as it was before this instruction
+            let uses_memory = a_bit || get_bit(dest_bits, 0);
+            let address = if uses_memory { Some(Computer::memory_index(self.a_register)?) } else { None };
+
             let x = self.d_register;
-            let y = if !a_bit {
-                self.a_register
-            } else {
-                self.memory[self.a_register as usize]
+            let y = match address {
+                Some(address) if a_bit => {
+                    access.read = Some((address as u16, self.memory[address]));
+                    self.memory[address]
+                }
+                _ => self.a_register
             };
-            let (alu_output, zr, ng) = self.alu(x, y, comp_bits);
+            let (alu_output, zr, ng) = alu(x, y, comp_bits);
 
+            if get_bit(dest_bits, 0) {
+                let address = address.unwrap();
+                snapshot.memory_write = Some((address as u16, self.memory[address]));
+                access.write = Some((address as u16, alu_output));
+                self.memory[address] = alu_output
+            }
             if get_bit(dest_bits, 2) {
                 self.a_register = alu_output
             }
             if get_bit(dest_bits, 1) {
                 self.d_register = alu_output
             }
-            if get_bit(dest_bits, 0) {
-                self.memory[self.a_register as usize] = alu_output
-            }
  
             let should_jump = match jump_bits {
               0 => false,        // null
@@ -92,9 +358,42 @@ impl Computer {
             self.pc = if should_jump {
                 self.a_register
             } else {
-                self.pc + 1
+                self.pc.wrapping_add(1)
+            }
+        }
+
+        if self.history_size > 0 {
+            if self.history.len() == self.history_size {
+                self.history.pop_front();
             }
+            self.history.push_back(snapshot);
         }
+        self.cycles += 1;
+        Ok(access)
     }
 
+    /// Detects the conventional `(END) @END 0;JMP` loop programs use to halt.
+    pub fn is_halted(&self) -> bool {
+        let instr_at = |address: i16| {
+            if address < 0 { None } else { self.rom.get(address as usize).copied().flatten() }
+        };
+        let is_a_instr_to = |instr: Option<i16>, address: i16| instr == Some(address);
+        let is_unconditional_jump = |instr: Option<i16>| match instr {
+            Some(instr) => get_bit(instr, 15)
+                && get_bit_slice(instr, 3, 6) == 0
+                && get_bit_slice(instr, 0, 3) == 0b111,
+            None => false
+        };
+
+        let pc = self.pc;
+        if is_a_instr_to(instr_at(pc), pc) {
+            // `@32767` in the last ROM cell has nothing after it to jump with
+            matches!(pc.checked_add(1), Some(next) if is_unconditional_jump(instr_at(next)))
+        } else if is_unconditional_jump(instr_at(pc)) {
+            self.a_register == pc
+                || (self.a_register == pc - 1 && is_a_instr_to(instr_at(pc - 1), pc - 1))
+        } else {
+            false
+        }
+    }
 }
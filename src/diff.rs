@@ -0,0 +1,140 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::computer::{Computer, Fault, ProgramTooLarge};
+use crate::trace::{self, TraceEntry};
+
+/// Where two executions stop agreeing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// How many instructions both executions agreed on
+    pub cycle: u64,
+    /// The last instructions before the divergence, as the left side executed them
+    pub context: Vec<TraceEntry>,
+    /// The next step of each side; `None` when it halted or its trace ended
+    pub left: Option<Result<TraceEntry, Fault>>,
+    pub right: Option<Result<TraceEntry, Fault>>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffResult {
+    /// Both executions ended together after `cycles` identical instructions
+    Identical { cycles: u64 },
+    Diverged(Divergence)
+}
+
+/// Executes a program one traced instruction at a time until it halts or faults.
+pub struct Execution {
+    computer: Computer,
+    stopped: bool
+}
+
+impl Execution {
+    pub fn new(program: &[i16]) -> Result<Execution, ProgramTooLarge> {
+        let mut computer = Computer::new();
+        computer.set_history_size(0);
+        computer.load(program)?;
+        Ok(Execution { computer, stopped: false })
+    }
+
+    pub fn computer(&self) -> &Computer {
+        &self.computer
+    }
+}
+
+impl Iterator for Execution {
+    type Item = Result<TraceEntry, Fault>;
+
+    fn next(&mut self) -> Option<Result<TraceEntry, Fault>> {
+        if self.stopped || self.computer.is_halted() {
+            return None;
+        }
+        match trace::step(&mut self.computer) {
+            Ok((entry, _)) => Some(Ok(entry)),
+            Err(fault) => {
+                self.stopped = true;
+                Some(Err(fault))
+            }
+        }
+    }
+}
+
+/// The parts of the CPU state two executed instructions disagree on.
+/// Only the effects count, so equivalent encodings of an instruction match.
+pub fn differences(left: &TraceEntry, right: &TraceEntry) -> Vec<&'static str> {
+    let mut differences = vec![];
+    if left.pc != right.pc {
+        differences.push("PC");
+    }
+    if left.a_register != right.a_register {
+        differences.push("A");
+    }
+    if left.d_register != right.d_register {
+        differences.push("D");
+    }
+    let write = |entry: &TraceEntry| entry.write.map(|write| (write.address, write.new));
+    if write(left) != write(right) {
+        differences.push("RAM write");
+    }
+    differences
+}
+
+/// Steps both executions in lockstep until they diverge or both end,
+/// keeping up to `context` agreeing instructions to show before a divergence.
+pub fn diff(
+    mut left: impl Iterator<Item = Result<TraceEntry, Fault>>,
+    mut right: impl Iterator<Item = Result<TraceEntry, Fault>>,
+    context: usize
+) -> DiffResult {
+    let mut history = VecDeque::with_capacity(context);
+    let mut cycle = 0;
+    loop {
+        let (left_step, right_step) = (left.next(), right.next());
+        let entry = match (&left_step, &right_step) {
+            (None, None) => return DiffResult::Identical { cycles: cycle },
+            (Some(Ok(left)), Some(Ok(right))) if differences(left, right).is_empty() => *left,
+            // The same fault on both sides still ends both executions the same way
+            (Some(Err(left)), Some(Err(right))) if left == right => {
+                return DiffResult::Identical { cycles: cycle };
+            }
+            _ => {
+                return DiffResult::Diverged(Divergence {
+                    cycle,
+                    context: history.into_iter().collect(),
+                    left: left_step,
+                    right: right_step
+                });
+            }
+        };
+        if context > 0 {
+            if history.len() == context {
+                history.pop_front();
+            }
+            history.push_back(entry);
+        }
+        cycle += 1;
+    }
+}
+
+fn write_step(f: &mut fmt::Formatter, marker: char, step: &Option<Result<TraceEntry, Fault>>) -> fmt::Result {
+    match step {
+        Some(Ok(entry)) => writeln!(f, "{} {}", marker, entry),
+        Some(Err(fault)) => writeln!(f, "{} error: {}", marker, fault),
+        None => writeln!(f, "{} (ended)", marker)
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "first difference after {} cycles", self.cycle)?;
+        if let (Some(Ok(left)), Some(Ok(right))) = (&self.left, &self.right) {
+            write!(f, " in {}", differences(left, right).join(", "))?;
+        }
+        writeln!(f)?;
+        for entry in &self.context {
+            writeln!(f, "  {}", entry)?;
+        }
+        write_step(f, '<', &self.left)?;
+        write_step(f, '>', &self.right)
+    }
+}
@@ -0,0 +1,68 @@
+use phf::{Map, phf_map};
+use crate::utils::{get_bit, get_bit_slice};
+
+pub(crate) static R_DEST_SYMBOLS: [&str; 8] = ["", "M", "D", "MD", "A", "AM", "AD", "AMD"];
+
+static R_COMP_SYMBOLS: Map<i16, &'static str> = phf_map! {
+    0b0101010i16 => "0",   0b0111111i16 => "1",   0b0111010i16 => "-1",  0b0001100i16 => "D",
+    0b0110000i16 => "A",   0b1110000i16 => "M",   0b0001101i16 => "!D",  0b0110001i16 => "!A",
+    0b1110001i16 => "!M",  0b0001111i16 => "-D" , 0b0110011i16 => "-A",  0b1110011i16 => "-M",
+    0b0011111i16 => "D+1", 0b0110111i16 => "A+1", 0b1110111i16 => "M+1", 0b0001110i16 => "D-1",
+    0b0110010i16 => "A-1", 0b1110010i16 => "M-1", 0b0000010i16 => "D+A", 0b1000010i16 => "D+M",
+    0b0010011i16 => "D-A", 0b1010011i16 => "D-M", 0b0000111i16 => "A-D", 0b1000111i16 => "M-D",
+    0b0000000i16 => "D&A", 0b1000000i16 => "D&M", 0b0010101i16 => "D|A", 0b1010101i16 => "D|M"
+};
+
+// Names of the comp bits, most significant first
+static CONTROL_BITS: [&str; 7] = ["a", "zx", "nx", "zy", "ny", "f", "no"];
+
+pub(crate) static R_JUMP_SYMBOLS: [&str; 8] = ["", "JGT", "JEQ", "JGE", "JLT", "JNE", "JLE", "JMP"];
+
+/// How the comp part of a C-instruction is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// The assembler mnemonic, e.g. `D=M+1`. Comp codes without a mnemonic
+    /// are shown as their raw `a c1..c6` bits, e.g. `D=comp(0000001)`.
+    Mnemonic,
+    /// The names of the ALU control bits that are set, e.g. `D={a zx nx ny f no}`.
+    ControlBits
+}
+
+pub fn to_asm(instr: i16) -> String {
+    disassemble(instr, Syntax::Mnemonic)
+}
+
+/// Renders any 16-bit word. Bits 13 and 14 of a C-instruction are ignored,
+/// just like the CPU does.
+pub fn disassemble(instr: i16, syntax: Syntax) -> String {
+    let is_a_instr = !get_bit(instr, 15);
+    if is_a_instr {
+        format!("@{}", instr)
+    }
+    else {
+        let comp_bits = get_bit_slice(instr, 6, 13);
+        let dest_bits = get_bit_slice(instr, 3, 6);
+        let jump_bits = get_bit_slice(instr, 0, 3);
+        let mut asm = "".to_owned();
+
+        if dest_bits != 0 {
+            asm.push_str(&format!("{}=", R_DEST_SYMBOLS[dest_bits as usize]));
+        }
+        match (syntax, R_COMP_SYMBOLS.get(&comp_bits)) {
+            (Syntax::Mnemonic, Some(comp)) => asm.push_str(comp),
+            (Syntax::Mnemonic, None) => asm.push_str(&format!("comp({:07b})", comp_bits)),
+            (Syntax::ControlBits, _) => {
+                let set_bits: Vec<&str> = CONTROL_BITS.iter().enumerate()
+                    .filter(|(i, _)| get_bit(comp_bits, 6 - *i as u16))
+                    .map(|(_, name)| *name)
+                    .collect();
+                asm.push_str(&format!("{{{}}}", set_bits.join(" ")));
+            }
+        }
+        if jump_bits != 0 {
+            asm.push_str(&format!(";{}", R_JUMP_SYMBOLS[jump_bits as usize]));
+        }
+        
+        asm
+    }
+}
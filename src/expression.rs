@@ -0,0 +1,267 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::computer::Computer;
+use crate::format::NumberFormat;
+
+/// A condition over the CPU state such as `pc == 42 && RAM[0] > 100`.
+///
+/// Operands are numbers (decimal or `0x` hex, up to 65535, where values over
+/// 32767 wrap to negative like they do in the registers, so `65535` and
+/// `0xFFFF` are both -1), the registers
+/// `A`, `D`, `PC` and `M`, RAM cells `RAM[<expr>]` and assembler symbols,
+/// which stand for their value like they do in `@symbol`, so `RAM[i]` is the
+/// variable `i`. The lowercase `a`, `d`, `pc` and `m` also name the registers
+/// unless the program defines a symbol with that name.
+/// The operators are, from loosest to tightest binding, `||`, `&&`, the
+/// comparisons `== != < <= > >=`, `+ -`, and the prefix `! -`. Comparisons
+/// and logical operators evaluate to 1 or 0; any non-zero value is true.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expression {
+    text: String,
+    node: Node
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Register {
+    A,
+    D,
+    Pc,
+    M
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Number(i64),
+    Register(Register),
+    Ram(Box<Node>),
+    Not(Box<Node>),
+    Negate(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(&'static str)
+}
+
+// Longer operators first so `<=` isn't read as `<`
+static OPERATORS: [&str; 16] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "+", "-", "!", "(", ")", "[", "]", "="
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap();
+        let len = if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$' || c == ':' {
+            let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || "_.$:".contains(c))).unwrap_or(rest.len());
+            let word = &rest[..len];
+            let token = if c.is_ascii_digit() {
+                let format = if word.starts_with("0x") { NumberFormat::Hex } else { NumberFormat::Unsigned };
+                let value = format.parse(word).ok_or_else(|| format!("invalid number: {}", word))?;
+                Token::Number(value as i64)
+            } else {
+                Token::Name(word.to_owned())
+            };
+            tokens.push(token);
+            len
+        } else {
+            let op = OPERATORS.iter().find(|op| rest.starts_with(*op))
+                .ok_or_else(|| format!("unexpected character: {}", c))?;
+            // A lone `=` reads like a comparison, the way test scripts write it
+            tokens.push(Token::Op(if *op == "=" { "==" } else { op }));
+            op.len()
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a, F: Fn(&str) -> Option<u16>> {
+    tokens: &'a [Token],
+    position: usize,
+    symbol: F
+}
+
+impl<'a, F: Fn(&str) -> Option<u16>> Parser<'a, F> {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.position) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.peek_op() == Some(op) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(format!("expected `{}`", op))
+        }
+    }
+
+    fn binary(&mut self, ops: &[(&str, BinaryOp)], operand: fn(&mut Self) -> Result<Node, String>) -> Result<Node, String> {
+        let mut left = operand(self)?;
+        while let Some(&(_, op)) = ops.iter().find(|(symbol, _)| self.peek_op() == Some(symbol)) {
+            self.position += 1;
+            let right = operand(self)?;
+            left = Node::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Node, String> {
+        self.binary(&[("||", BinaryOp::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Node, String> {
+        self.binary(&[("&&", BinaryOp::And)], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Node, String> {
+        self.binary(&[
+            ("==", BinaryOp::Eq), ("!=", BinaryOp::Ne), ("<", BinaryOp::Lt),
+            ("<=", BinaryOp::Le), (">", BinaryOp::Gt), (">=", BinaryOp::Ge)
+        ], Self::sum)
+    }
+
+    fn sum(&mut self) -> Result<Node, String> {
+        self.binary(&[("+", BinaryOp::Add), ("-", BinaryOp::Sub)], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        match self.peek_op() {
+            Some("!") => {
+                self.position += 1;
+                Ok(Node::Not(Box::new(self.unary()?)))
+            }
+            Some("-") => {
+                self.position += 1;
+                Ok(Node::Negate(Box::new(self.unary()?)))
+            }
+            _ => self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        let token = self.tokens.get(self.position).cloned().ok_or("unexpected end of expression")?;
+        self.position += 1;
+        match token {
+            Token::Number(value) => Ok(Node::Number(value)),
+            Token::Op("(") => {
+                let node = self.or()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Token::Name(name) => match name.as_str() {
+                "A" => Ok(Node::Register(Register::A)),
+                "D" => Ok(Node::Register(Register::D)),
+                "PC" => Ok(Node::Register(Register::Pc)),
+                "M" => Ok(Node::Register(Register::M)),
+                "RAM" | "ram" if self.peek_op() == Some("[") => {
+                    self.position += 1;
+                    let address = self.or()?;
+                    self.expect("]")?;
+                    Ok(Node::Ram(Box::new(address)))
+                }
+                // Program symbols win over the lowercase register names
+                _ => match ((self.symbol)(&name), name.as_str()) {
+                    (Some(value), _) => Ok(Node::Number(value as i64)),
+                    (None, "a") => Ok(Node::Register(Register::A)),
+                    (None, "d") => Ok(Node::Register(Register::D)),
+                    (None, "pc") => Ok(Node::Register(Register::Pc)),
+                    (None, "m") => Ok(Node::Register(Register::M)),
+                    (None, _) => Err(format!("unknown symbol: {}", name))
+                }
+            },
+            Token::Op(op) => Err(format!("unexpected `{}`", op))
+        }
+    }
+}
+
+impl Expression {
+    /// Parses an expression, resolving symbols with `symbol`.
+    pub fn parse(text: &str, symbol: impl Fn(&str) -> Option<u16>) -> Result<Expression, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens: &tokens, position: 0, symbol };
+        let node = parser.or()?;
+        match tokens.get(parser.position) {
+            None => Ok(Expression { text: text.trim().to_owned(), node }),
+            Some(Token::Number(value)) => Err(format!("unexpected `{}`", value)),
+            Some(Token::Name(name)) => Err(format!("unexpected `{}`", name)),
+            Some(Token::Op(op)) => Err(format!("unexpected `{}`", op))
+        }
+    }
+
+    /// Returns `None` if the expression reads outside of RAM or overflows.
+    pub fn evaluate(&self, computer: &Computer) -> Option<i64> {
+        evaluate(&self.node, computer)
+    }
+
+    pub fn is_true(&self, computer: &Computer) -> bool {
+        matches!(self.evaluate(computer), Some(value) if value != 0)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+fn read_memory(computer: &Computer, address: i64) -> Option<i64> {
+    let address = usize::try_from(address).ok()?;
+    computer.memory.get(address).map(|&value| value as i64)
+}
+
+fn evaluate(node: &Node, computer: &Computer) -> Option<i64> {
+    Some(match node {
+        Node::Number(value) => *value,
+        Node::Register(Register::A) => computer.a_register as i64,
+        Node::Register(Register::D) => computer.d_register as i64,
+        Node::Register(Register::Pc) => computer.pc as i64,
+        Node::Register(Register::M) => read_memory(computer, computer.a_register as u16 as i64)?,
+        Node::Ram(address) => read_memory(computer, evaluate(address, computer)?)?,
+        Node::Not(node) => (evaluate(node, computer)? == 0) as i64,
+        Node::Negate(node) => evaluate(node, computer)?.checked_neg()?,
+        Node::Binary(BinaryOp::Or, left, right) => {
+            (evaluate(left, computer)? != 0 || evaluate(right, computer)? != 0) as i64
+        }
+        Node::Binary(BinaryOp::And, left, right) => {
+            (evaluate(left, computer)? != 0 && evaluate(right, computer)? != 0) as i64
+        }
+        Node::Binary(op, left, right) => {
+            let left = evaluate(left, computer)?;
+            let right = evaluate(right, computer)?;
+            match op {
+                BinaryOp::Eq => (left == right) as i64,
+                BinaryOp::Ne => (left != right) as i64,
+                BinaryOp::Lt => (left < right) as i64,
+                BinaryOp::Le => (left <= right) as i64,
+                BinaryOp::Gt => (left > right) as i64,
+                BinaryOp::Ge => (left >= right) as i64,
+                BinaryOp::Add => left.checked_add(right)?,
+                BinaryOp::Sub => left.checked_sub(right)?,
+                BinaryOp::Or | BinaryOp::And => unreachable!()
+            }
+        }
+    })
+}
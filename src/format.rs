@@ -0,0 +1,84 @@
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberFormat {
+    Decimal,
+    Unsigned,
+    Hex,
+    Binary
+}
+
+impl NumberFormat {
+    pub fn format(self, value: i16) -> String {
+        match self {
+            NumberFormat::Decimal => value.to_string(),
+            NumberFormat::Unsigned => (value as u16).to_string(),
+            NumberFormat::Hex => format!("0x{:04X}", value as u16),
+            NumberFormat::Binary => format!("{:016b}", value as u16)
+        }
+    }
+
+    /// Parses a value written in this format. The `0x` and `0b` prefixes are optional.
+    pub fn parse(self, input: &str) -> Option<i16> {
+        let input = input.trim();
+        match self {
+            NumberFormat::Decimal => input.parse().ok(),
+            NumberFormat::Unsigned => input.parse::<u16>().ok().map(|value| value as i16),
+            NumberFormat::Hex => {
+                let digits = input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")).unwrap_or(input);
+                u16::from_str_radix(digits, 16).ok().map(|value| value as i16)
+            }
+            NumberFormat::Binary => {
+                let digits = input.strip_prefix("0b").unwrap_or(input);
+                u16::from_str_radix(digits, 2).ok().map(|value| value as i16)
+            }
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NumberFormat::Decimal => "dec",
+            NumberFormat::Unsigned => "udec",
+            NumberFormat::Hex => "hex",
+            NumberFormat::Binary => "bin"
+        }
+    }
+}
+
+impl FromStr for NumberFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<NumberFormat, String> {
+        match s {
+            "dec" | "decimal" => Ok(NumberFormat::Decimal),
+            "udec" | "unsigned" => Ok(NumberFormat::Unsigned),
+            "hex" => Ok(NumberFormat::Hex),
+            "bin" | "binary" => Ok(NumberFormat::Binary),
+            _ => Err(format!("unknown format `{}` (expected dec, udec, hex or bin)", s))
+        }
+    }
+}
+
+/// What a value means as a character of the Hack character set, which is
+/// ASCII for printable characters plus codes from 128 up for special keys.
+pub fn character(value: i16) -> Option<String> {
+    let name = match value {
+        32..=126 => return Some(format!("'{}'", value as u8 as char)),
+        128 => "newline",
+        129 => "backspace",
+        130 => "left arrow",
+        131 => "up arrow",
+        132 => "right arrow",
+        133 => "down arrow",
+        134 => "home",
+        135 => "end",
+        136 => "page up",
+        137 => "page down",
+        138 => "insert",
+        139 => "delete",
+        140 => "esc",
+        141..=152 => return Some(format!("F{}", value - 140)),
+        _ => return None
+    };
+    Some(name.to_owned())
+}
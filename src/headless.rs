@@ -0,0 +1,93 @@
+use std::ops::Range;
+
+use crate::computer::{Computer, Fault, MemoryAccess, RAM_SIZE};
+use crate::format::NumberFormat;
+
+pub const DEFAULT_MAX_CYCLES: u64 = 10_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dump {
+    ARegister,
+    DRegister,
+    Pc,
+    Memory(Range<usize>)
+}
+
+/// Parses a comma separated list such as `A,D,PC,RAM[0],RAM[256..260]`.
+/// Ranges are exclusive and must lie within RAM.
+pub fn parse_dumps(spec: &str) -> Result<Vec<Dump>, String> {
+    spec.split(',')
+        .map(|item| {
+            let item = item.trim();
+            match item {
+                "A" => Ok(Dump::ARegister),
+                "D" => Ok(Dump::DRegister),
+                "PC" => Ok(Dump::Pc),
+                _ => parse_memory_range(item)
+                    .map(Dump::Memory)
+                    .ok_or_else(|| format!("invalid dump: {}", item))
+            }
+        })
+        .collect()
+}
+
+fn parse_memory_range(item: &str) -> Option<Range<usize>> {
+    let range = item.strip_prefix("RAM[")?.strip_suffix(']')?;
+    match range.find("..") {
+        Some(sep) => {
+            let start = range[..sep].parse().ok()?;
+            let end = range[sep + 2..].parse().ok()?;
+            if start < end && end <= RAM_SIZE { Some(start..end) } else { None }
+        }
+        None => {
+            let address: usize = range.parse().ok()?;
+            if address < RAM_SIZE { Some(address..address + 1) } else { None }
+        }
+    }
+}
+
+pub fn default_dumps() -> Vec<Dump> {
+    vec![Dump::ARegister, Dump::DRegister, Dump::Pc, Dump::Memory(0..16)]
+}
+
+/// Runs until the program halts, faults or `max_cycles` have been executed.
+/// Returns whether the program halted.
+pub fn run(computer: &mut Computer, max_cycles: u64) -> Result<bool, Fault> {
+    run_with(computer, max_cycles, Computer::step)
+}
+
+/// Like `run`, but executes each instruction with `step`, e.g. to trace it.
+pub fn run_with(
+    computer: &mut Computer,
+    max_cycles: u64,
+    mut step: impl FnMut(&mut Computer) -> Result<MemoryAccess, Fault>
+) -> Result<bool, Fault> {
+    while computer.cycles < max_cycles {
+        if computer.is_halted() {
+            return Ok(true);
+        }
+        step(computer)?;
+    }
+    Ok(computer.is_halted())
+}
+
+/// Formats the requested registers and memory, one `NAME: value` per line.
+pub fn format_dumps(computer: &Computer, dumps: &[Dump], format: NumberFormat) -> String {
+    let mut out = String::new();
+    for dump in dumps {
+        match dump {
+            Dump::ARegister => out.push_str(&format!("A: {}\n", format.format(computer.a_register))),
+            Dump::DRegister => out.push_str(&format!("D: {}\n", format.format(computer.d_register))),
+            Dump::Pc => out.push_str(&format!("PC: {}\n", format.format(computer.pc))),
+            Dump::Memory(range) => {
+                for address in range.clone() {
+                    match computer.memory.get(address) {
+                        Some(value) => out.push_str(&format!("RAM[{}]: {}\n", address, format.format(*value))),
+                        None => out.push_str(&format!("RAM[{}]: -\n", address))
+                    }
+                }
+            }
+        }
+    }
+    out
+}
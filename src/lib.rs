@@ -0,0 +1,23 @@
+//! An emulator for the Hack computer from Nand2tetris.
+//!
+//! The library contains the CPU ([`computer`]), the assembler ([`assembler`]),
+//! the disassembler ([`disassembler`]) and the headless tooling built on top of
+//! them. The terminal UI lives in the `hack-cpu-emulator` binary and is only
+//! built with the default `tui` feature; the binary's headless commands are
+//! always available.
+
+pub mod assembler;
+pub mod computer;
+pub mod diff;
+pub mod disassembler;
+pub mod expression;
+pub mod format;
+pub mod headless;
+pub mod loader;
+pub mod profile;
+pub mod test_script;
+pub mod trace;
+// Helpers shared with the binary, not part of the library's API
+#[doc(hidden)]
+pub mod utils;
+pub mod watchpoint;
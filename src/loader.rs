@@ -0,0 +1,143 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::assembler::{assemble, AssemblyError, Symbol};
+use crate::computer::ROM_SIZE;
+use crate::utils::lines_from_file;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io { path: PathBuf, error: io::Error },
+    InvalidBinary { line: usize, content: String },
+    TooLarge { instructions: usize },
+    Assembly { path: PathBuf, source: Vec<String>, errors: Vec<AssemblyError> }
+}
+
+impl LoadError {
+    /// Like `Display`, but assembly errors are rendered compiler-style with
+    /// the offending source lines.
+    pub fn report(&self) -> String {
+        match self {
+            LoadError::Assembly { path, source, errors } => {
+                let filename = path.display().to_string();
+                let mut out = String::new();
+                for error in errors {
+                    out.push_str(&error.render(&filename, source));
+                    out.push_str("\n\n");
+                }
+                out.push_str(&format!(
+                    "error: could not assemble {} due to {} error{}",
+                    filename,
+                    errors.len(),
+                    if errors.len() == 1 { "" } else { "s" }
+                ));
+                out
+            }
+            _ => format!("error: {}", self)
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            LoadError::InvalidBinary { line, content } => write!(
+                f,
+                "invalid binary instruction on line {}: {:?} (expected 16 '0' or '1' characters)",
+                line,
+                content
+            ),
+            LoadError::TooLarge { instructions } => write!(
+                f,
+                "program of {} instructions does not fit in the {} words of ROM",
+                instructions,
+                ROM_SIZE
+            ),
+            LoadError::Assembly { path, errors, .. } => {
+                let errors: Vec<String> = errors.iter()
+                    .map(|error| format!("{}:{}", path.display(), error))
+                    .collect();
+                write!(f, "{}", errors.join("\n"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+fn is_binary_instruction(line: &str) -> bool {
+    line.len() == 16 && line.chars().all(|c| c == '0' || c == '1')
+}
+
+/// A file is treated as a `.hack` binary if it has that extension, or if it
+/// isn't an `.asm` file and every non-empty line is a binary instruction.
+fn is_hack_file(path: &Path, input: &[String]) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("hack") => true,
+        Some("asm") => false,
+        _ => {
+            let mut lines = input.iter().map(|l| l.trim()).filter(|l| !l.is_empty()).peekable();
+            lines.peek().is_some() && lines.all(is_binary_instruction)
+        }
+    }
+}
+
+pub fn parse_hack(input: &[String]) -> Result<Vec<i16>, LoadError> {
+    input.iter()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| {
+            if is_binary_instruction(line) {
+                Ok(u16::from_str_radix(line, 2).unwrap() as i16)
+            } else {
+                Err(LoadError::InvalidBinary { line: i + 1, content: line.to_owned() })
+            }
+        })
+        .collect()
+}
+
+/// A loaded program along with its source, if it was assembled.
+pub struct Program {
+    pub code: Vec<i16>,
+    pub source: Option<Source>
+}
+
+/// The assembly source of a program and how its instructions map onto it.
+pub struct Source {
+    pub lines: Vec<String>,
+    /// The 0-based source line each instruction was assembled from
+    pub source_map: Vec<usize>,
+    pub symbols: Vec<Symbol>
+}
+
+/// Reads either an assembly file or a pre-assembled `.hack` file.
+pub fn load_program(path: &Path) -> Result<Vec<i16>, LoadError> {
+    load_program_with_source(path).map(|program| program.code)
+}
+
+/// Like `load_program`, but keeps the source of assembly files for debugging.
+pub fn load_program_with_source(path: &Path) -> Result<Program, LoadError> {
+    let input = lines_from_file(path).map_err(|error| LoadError::Io { path: path.to_owned(), error })?;
+    let program = if is_hack_file(path, &input) {
+        Program { code: parse_hack(&input)?, source: None }
+    } else {
+        match assemble(&input) {
+            Ok(assembly) => Program {
+                code: assembly.code,
+                source: Some(Source {
+                    lines: input,
+                    source_map: assembly.source_map,
+                    symbols: assembly.symbols
+                })
+            },
+            Err(errors) => return Err(LoadError::Assembly { path: path.to_owned(), source: input, errors })
+        }
+    };
+    if program.code.len() > ROM_SIZE {
+        return Err(LoadError::TooLarge { instructions: program.code.len() });
+    }
+    Ok(program)
+}
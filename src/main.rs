@@ -1,36 +1,136 @@
 use std::env;
+use std::process;
+use std::fs::{self, File};
 use std::path::Path;
-use std::io::{stdout, Write};
-use std::time::Duration;
+use std::io::{self, stdout, BufReader, BufWriter, Write};
+#[cfg(feature = "tui")]
+use std::time::{Duration, Instant};
 
+#[cfg(feature = "tui")]
 use tui::Terminal;
+#[cfg(feature = "tui")]
 use tui::backend::CrosstermBackend;
+#[cfg(feature = "tui")]
 use crossterm::event::{poll, read, Event};
+#[cfg(feature = "tui")]
 use crossterm::{execute, cursor, terminal::{EnterAlternateScreen, LeaveAlternateScreen}};
 
-mod assembler;
-mod computer;
-mod utils;
+#[cfg(feature = "tui")]
 mod app;
+mod cli;
 
-use assembler::assemble;
-use utils::lines_from_file;
+use hack_cpu_emulator::{headless, profile, test_script};
+use hack_cpu_emulator::assembler::{assemble, predefined_symbols};
+use hack_cpu_emulator::disassembler::disassemble;
+use hack_cpu_emulator::loader::{load_program, load_program_with_source, LoadError, Program};
+use hack_cpu_emulator::utils::lines_from_file;
+use hack_cpu_emulator::computer::{Computer, Fault};
+use hack_cpu_emulator::diff::{self, DiffResult, Execution};
+use hack_cpu_emulator::trace::{TraceEntry, TraceReader, TraceWriter};
+#[cfg(feature = "tui")]
 use app::App;
+use cli::{Command, TuiOptions, RunOptions, AssembleOptions, DisassembleOptions, TraceOptions, DiffOptions};
+
+#[cfg(feature = "tui")]
+const FRAME_DURATION: Duration = Duration::from_millis(16);
+#[cfg(feature = "tui")]
+const KEY_RELEASE_DURATION: Duration = Duration::from_millis(200);
+/// Instructions listed in the profile of a headless run
+const PROFILE_HOT_SPOTS: usize = 20;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let command = match cli::parse_args(env::args().skip(1)) {
+        Ok(command) => command,
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    match command {
+        Command::Help => println!("{}", cli::USAGE),
+        Command::Version => println!("hack-cpu-emulator {}", env!("CARGO_PKG_VERSION")),
+        Command::Tui(options) => run_tui(options)?,
+        Command::Run(options) => run_headless(options),
+        Command::Assemble(options) => {
+            if let Err(error) = assemble_file(options) {
+                eprintln!("{}", error.report());
+                process::exit(1);
+            }
+        }
+        Command::Disassemble(options) => {
+            if let Err(error) = disassemble_file(options) {
+                eprintln!("{}", error.report());
+                process::exit(1);
+            }
+        }
+        Command::Test(path) => match test_script::run_test_script(&path, |text| println!("{}", text)) {
+            Ok(()) => println!("End of script - Comparison ended successfully"),
+            Err(error) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+        }
+        Command::Trace(path) => {
+            if let Err(error) = print_trace(&path) {
+                eprintln!("error: {}: {}", path.display(), error);
+                process::exit(1);
+            }
+        }
+        Command::Diff(options) => diff_files(options)
+    }
+
+    Ok(())
+}
+
+fn load_or_exit(path: &Path) -> Program {
+    match load_program_with_source(path) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("{}", error.report());
+            process::exit(1);
+        }
+    }
+}
+
+fn create_trace_or_exit(options: &TraceOptions) -> TraceWriter<BufWriter<File>> {
+    let trace = File::create(&options.path)
+        .and_then(|file| TraceWriter::new(BufWriter::new(file), options.format));
+    match trace {
+        Ok(trace) => trace,
+        Err(error) => {
+            eprintln!("error: {}: {}", options.path.display(), error);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_tui(_options: TuiOptions) -> Result<(), Box<dyn std::error::Error>> {
+    eprintln!("error: this build of hack-cpu-emulator has no terminal UI (enable the `tui` feature)");
+    process::exit(2);
+}
+
+#[cfg(feature = "tui")]
+fn run_tui(options: TuiOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let filename = options.path.file_name().unwrap_or_default().to_string_lossy();
+    let program = load_or_exit(&options.path);
 
-    let args: Vec<String> = env::args().collect();
-    let filename = Path::new(&args[1]).file_name().unwrap().to_string_lossy();
-    let input = lines_from_file(&args[1]).expect("could not read file");
-    let program = assemble(&input);
     let mut app = App::new(filename.to_string(), program);
+    app.set_history_size(options.history_size);
+    app.set_speed(options.speed);
+    if let Some(trace) = &options.trace {
+        app.set_trace(create_trace_or_exit(trace));
+    }
 
     let mut stdout = stdout();
     execute!(stdout, EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
+    let mut last_key_event = Instant::now();
 
     loop {
+        app.tick();
         terminal.draw(|mut f| app.draw(&mut f))?;
 
         match app.cursor_pos {
@@ -44,8 +144,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         };
         std::io::stdout().flush().ok();
 
-        if poll(Duration::from_millis(200))? {
+        let timeout = if app.is_running() { FRAME_DURATION } else { KEY_RELEASE_DURATION };
+        if poll(timeout)? {
             if let Event::Key(key) = read()? {
+                last_key_event = Instant::now();
                 let quit = app.handle_input_event(key.code);
                 if quit {
                     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
@@ -53,10 +155,167 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     break;
                 }
             }
-        } else {
+        } else if last_key_event.elapsed() >= KEY_RELEASE_DURATION {
             app.clear_input_event();
         }
     }
 
+    app.finish_trace()?;
+    Ok(())
+}
+
+/// Writes the program to `-o`, or to `<name>.hack` next to the source file,
+/// and optionally a `.lst` listing and a `.sym` symbol table beside it.
+fn assemble_file(options: AssembleOptions) -> Result<(), LoadError> {
+    let AssembleOptions { path, output, listing, symbols } = options;
+    let path = path.as_path();
+    let io_error = |path: &Path| {
+        let path = path.to_owned();
+        move |error| LoadError::Io { path, error }
+    };
+
+    let source = lines_from_file(path).map_err(io_error(path))?;
+    let assembly = match assemble(&source) {
+        Ok(assembly) => assembly,
+        Err(errors) => return Err(LoadError::Assembly { path: path.to_owned(), source, errors })
+    };
+
+    let output = output.unwrap_or_else(|| path.with_extension("hack"));
+    let binary: String = assembly.code.iter().map(|instr| format!("{:016b}\n", *instr as u16)).collect();
+    fs::write(&output, binary).map_err(io_error(&output))?;
+
+    if listing {
+        let listing_path = output.with_extension("lst");
+        fs::write(&listing_path, assembly.listing(&source)).map_err(io_error(&listing_path))?;
+    }
+    if symbols {
+        let symbols_path = output.with_extension("sym");
+        fs::write(&symbols_path, assembly.symbol_dump()).map_err(io_error(&symbols_path))?;
+    }
+    Ok(())
+}
+
+fn disassemble_file(options: DisassembleOptions) -> Result<(), LoadError> {
+    let program = load_program(&options.path)?;
+    let listing: String = program.iter().enumerate()
+        .map(|(address, instr)| {
+            let asm = disassemble(*instr, options.syntax);
+            match options.format {
+                Some(format) => format!("{:<20} // {}: {}\n", asm, address, format.format(*instr)),
+                None => format!("{}\n", asm)
+            }
+        })
+        .collect();
+
+    match options.output {
+        Some(output) => fs::write(&output, listing).map_err(|error| LoadError::Io { path: output, error }),
+        None => {
+            print!("{}", listing);
+            Ok(())
+        }
+    }
+}
+
+fn run_headless(options: RunOptions) {
+    let program = load_or_exit(&options.path);
+    let mut computer = Computer::new();
+    computer.set_history_size(0);
+    computer.set_profiling(options.profile);
+    computer.load(&program.code).expect("the loader rejects programs larger than ROM");
+
+    let result = match &options.trace {
+        Some(trace_options) => {
+            let mut trace = create_trace_or_exit(trace_options);
+            let result = headless::run_with(&mut computer, options.max_cycles, |computer| trace.step(computer));
+            if let Err(error) = trace.finish() {
+                eprintln!("error: {}: {}", trace_options.path.display(), error);
+                process::exit(1);
+            }
+            result
+        }
+        None => headless::run(&mut computer, options.max_cycles)
+    };
+    match result {
+        Ok(true) => eprintln!("halted after {} cycles", computer.cycles),
+        Ok(false) => eprintln!("stopped after {} cycles", computer.cycles),
+        Err(fault) => {
+            eprintln!("error: {} after {} cycles", fault, computer.cycles);
+            print_results(&computer, &program, &options);
+            process::exit(fault.exit_code());
+        }
+    }
+    print_results(&computer, &program, &options);
+}
+
+fn print_results(computer: &Computer, program: &Program, options: &RunOptions) {
+    print!("{}", headless::format_dumps(computer, &options.dumps, options.format));
+    if let Some(counts) = computer.profile() {
+        let symbols = match &program.source {
+            Some(source) => source.symbols.clone(),
+            None => predefined_symbols()
+        };
+        print!("\n{}", profile::report(counts, &computer.rom[..], &symbols, PROFILE_HOT_SPOTS));
+    }
+}
+
+fn print_trace(path: &Path) -> std::io::Result<()> {
+    let reader = TraceReader::new(BufReader::new(File::open(path)?))?;
+    let stdout = stdout();
+    let mut out = stdout.lock();
+    for entry in reader {
+        writeln!(out, "{}", entry?)?;
+    }
     Ok(())
 }
+
+type Step = Result<TraceEntry, Fault>;
+
+/// Reads a binary trace, or executes the program if the file isn't one.
+fn open_diff_input(path: &Path) -> Box<dyn Iterator<Item = io::Result<Step>>> {
+    if let Ok(reader) = File::open(path).and_then(|file| TraceReader::new(BufReader::new(file))) {
+        return Box::new(reader.map(|entry| entry.map(Ok)));
+    }
+    match load_program(path) {
+        Ok(program) => {
+            let execution = Execution::new(&program).expect("the loader rejects programs larger than ROM");
+            Box::new(execution.map(Ok))
+        }
+        Err(error) => {
+            eprintln!("{}", error.report());
+            process::exit(2);
+        }
+    }
+}
+
+/// Ends `input` at its first read error, which is kept in `error`.
+fn until_error<'a>(
+    input: Box<dyn Iterator<Item = io::Result<Step>>>,
+    error: &'a mut Option<io::Error>
+) -> impl Iterator<Item = Step> + 'a {
+    input.map_while(move |step| step.map_err(|e| *error = Some(e)).ok())
+}
+
+fn diff_files(options: DiffOptions) {
+    let left = open_diff_input(&options.left).take(options.max_cycles as usize);
+    let right = open_diff_input(&options.right).take(options.max_cycles as usize);
+    let (mut left_error, mut right_error) = (None, None);
+    let result = diff::diff(
+        until_error(Box::new(left), &mut left_error),
+        until_error(Box::new(right), &mut right_error),
+        options.context
+    );
+
+    for (path, error) in [(&options.left, left_error), (&options.right, right_error)] {
+        if let Some(error) = error {
+            eprintln!("error: {}: {}", path.display(), error);
+            process::exit(2);
+        }
+    }
+    match result {
+        DiffResult::Identical { cycles } => println!("no differences in {} cycles", cycles),
+        DiffResult::Diverged(divergence) => {
+            print!("{}", divergence);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use crate::assembler::{Symbol, SymbolKind};
+use crate::disassembler::to_asm;
+
+/// The name given to code before the first label
+const START: &str = "(start)";
+
+/// The cycles spent in the code between a label and the next one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelProfile {
+    pub name: String,
+    pub address: usize,
+    pub cycles: u64
+}
+
+/// Executed addresses with their counts, most executed first.
+pub fn hot_spots(profile: &[u64]) -> Vec<(usize, u64)> {
+    let mut hot_spots: Vec<(usize, u64)> = profile.iter().copied().enumerate()
+        .filter(|&(_, count)| count > 0)
+        .collect();
+    hot_spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    hot_spots
+}
+
+/// Sums the counts of every address under the closest label before it,
+/// most expensive first. Labels the VM translator generates inside a
+/// function, such as `Main.run$WHILE_EXP0` or `Main.run$ret.1`, count
+/// toward the function, so compiled Jack code is profiled by function.
+pub fn by_label(profile: &[u64], symbols: &[Symbol]) -> Vec<LabelProfile> {
+    let mut labels: Vec<(usize, &str)> = symbols.iter()
+        .filter(|symbol| symbol.kind == SymbolKind::Label)
+        .map(|symbol| (symbol.value as usize, symbol.name.split('$').next().unwrap()))
+        .collect();
+    labels.sort();
+
+    let first = labels.first().map_or(profile.len(), |&(address, _)| address.min(profile.len()));
+    let mut regions: Vec<(&str, usize, u64)> = vec![(START, 0, profile[..first].iter().sum())];
+    for (i, &(address, name)) in labels.iter().enumerate() {
+        let end = labels.get(i + 1).map_or(profile.len(), |&(next, _)| next).min(profile.len());
+        regions.push((name, address, profile[address.min(end)..end].iter().sum()));
+    }
+
+    let mut totals: Vec<LabelProfile> = vec![];
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for (name, address, cycles) in regions {
+        let i = *index.entry(name).or_insert_with(|| {
+            totals.push(LabelProfile { name: name.to_owned(), address, cycles: 0 });
+            totals.len() - 1
+        });
+        totals[i].cycles += cycles;
+    }
+
+    totals.retain(|label| label.cycles > 0);
+    totals.sort_by(|a, b| b.cycles.cmp(&a.cycles).then(a.address.cmp(&b.address)));
+    totals
+}
+
+fn percent(count: u64, total: u64) -> f64 {
+    if total == 0 { 0.0 } else { count as f64 * 100.0 / total as f64 }
+}
+
+/// A readable report of the cycles spent per label, if the program has
+/// any, and of the `top` most executed instructions.
+pub fn report(profile: &[u64], rom: &[Option<i16>], symbols: &[Symbol], top: usize) -> String {
+    let total: u64 = profile.iter().sum();
+    let mut out = format!("{} cycles\n", total);
+
+    if symbols.iter().any(|symbol| symbol.kind == SymbolKind::Label) {
+        out.push_str(&format!("\n{:>10} {:>7}  label\n", "cycles", "%"));
+        for label in by_label(profile, symbols) {
+            out.push_str(&format!("{:>10} {:>6.1}%  {}\n", label.cycles, percent(label.cycles, total), label.name));
+        }
+    }
+
+    out.push_str(&format!("\n{:>10} {:>7}  {:>7}  instruction\n", "count", "%", "address"));
+    for (address, count) in hot_spots(profile).into_iter().take(top) {
+        let instruction = rom.get(address).copied().flatten().map(to_asm).unwrap_or_default();
+        out.push_str(&format!("{:>10} {:>6.1}%  {:>7}  {}\n", count, percent(count, total), address, instruction));
+    }
+    out
+}
@@ -0,0 +1,509 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::computer::Computer;
+use crate::loader::load_program;
+use crate::utils::lines_from_file;
+
+#[derive(Debug)]
+pub enum TestError {
+    Io { path: PathBuf, error: io::Error },
+    Syntax { line: usize, message: String },
+    Runtime { line: usize, message: String },
+    ComparisonFailure { line: usize }
+}
+
+impl fmt::Display for TestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TestError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            TestError::Syntax { line, message } => write!(f, "In line {}, {}", line, message),
+            TestError::Runtime { line, message } => write!(f, "In line {}, {}", line, message),
+            TestError::ComparisonFailure { line } => write!(f, "Comparison failure at line {}", line)
+        }
+    }
+}
+
+impl std::error::Error for TestError {}
+
+#[derive(Debug, Clone, Copy)]
+enum Variable {
+    A,
+    D,
+    Pc,
+    Time,
+    Ram(usize),
+    Rom(usize)
+}
+
+#[derive(Debug, Clone)]
+struct Column {
+    name: String,
+    variable: Variable,
+    format: char,
+    pad_left: usize,
+    len: usize,
+    pad_right: usize
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Comparison {
+    Eq, Ne, Lt, Le, Gt, Ge
+}
+
+#[derive(Debug)]
+enum Command {
+    Load(String),
+    OutputFile(String),
+    CompareTo(String),
+    OutputList(Vec<Column>),
+    Set(Variable, i16),
+    Repeat(u64, Vec<Statement>),
+    While(Variable, Comparison, i16, Vec<Statement>),
+    Tick,
+    Tock,
+    TickTock,
+    Output,
+    Echo(String),
+    ClearEcho
+}
+
+#[derive(Debug)]
+struct Statement {
+    command: Command,
+    line: usize
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Separator,
+    Open,
+    Close
+}
+
+fn syntax_error<T>(line: usize, message: impl Into<String>) -> Result<T, TestError> {
+    Err(TestError::Syntax { line, message: message.into() })
+}
+
+fn lex(input: &[String]) -> Vec<(Token, usize)> {
+    let mut tokens = vec![];
+    let mut in_comment = false;
+
+    for (line_number, line) in input.iter().enumerate() {
+        let line_number = line_number + 1;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if in_comment {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    in_comment = false;
+                }
+                continue;
+            }
+            match c {
+                '/' if chars.peek() == Some(&'/') => break,
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    in_comment = true;
+                }
+                ',' | ';' => tokens.push((Token::Separator, line_number)),
+                '{' => tokens.push((Token::Open, line_number)),
+                '}' => tokens.push((Token::Close, line_number)),
+                '"' => {
+                    let text: String = chars.by_ref().take_while(|&c| c != '"').collect();
+                    tokens.push((Token::Str(text), line_number));
+                }
+                c if c.is_whitespace() => {}
+                c => {
+                    let mut word = c.to_string();
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() || ",;{}\"".contains(c) {
+                            break;
+                        }
+                        word.push(c);
+                        chars.next();
+                    }
+                    tokens.push((Token::Word(word), line_number));
+                }
+            }
+        }
+    }
+
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens.get(self.position)
+            .or_else(|| self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).map(|(token, _)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    fn word(&mut self, expected: &str) -> Result<String, TestError> {
+        let line = self.line();
+        match self.next() {
+            Some(Token::Word(word)) => Ok(word),
+            _ => syntax_error(line, format!("expected {}", expected))
+        }
+    }
+
+    fn end_of_command(&mut self) -> Result<(), TestError> {
+        let line = self.line();
+        match self.next() {
+            Some(Token::Separator) => Ok(()),
+            _ => syntax_error(line, "expected ',' or ';'")
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<Statement>, TestError> {
+        let line = self.line();
+        if self.next() != Some(Token::Open) {
+            return syntax_error(line, "expected '{'");
+        }
+        let mut statements = vec![];
+        loop {
+            match self.peek() {
+                Some(Token::Close) => {
+                    self.next();
+                    return Ok(statements);
+                }
+                Some(_) => statements.push(self.statement()?),
+                None => return syntax_error(self.line(), "expected '}'")
+            }
+        }
+    }
+
+    fn statements(&mut self) -> Result<Vec<Statement>, TestError> {
+        let mut statements = vec![];
+        while self.peek().is_some() {
+            statements.push(self.statement()?);
+        }
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Statement, TestError> {
+        let line = self.line();
+        let name = self.word("a command")?;
+        let command = match name.as_str() {
+            "load" => Command::Load(self.word("a file name")?),
+            "output-file" => Command::OutputFile(self.word("a file name")?),
+            "compare-to" => Command::CompareTo(self.word("a file name")?),
+            "output-list" => {
+                let mut columns = vec![];
+                while let Some(Token::Word(_)) = self.peek() {
+                    let spec = self.word("a variable")?;
+                    columns.push(parse_column(&spec).ok_or_else(|| TestError::Syntax {
+                        line,
+                        message: format!("illegal output format: {}", spec)
+                    })?);
+                }
+                Command::OutputList(columns)
+            }
+            "set" => {
+                let variable = self.word("a variable")?;
+                let variable = parse_variable(&variable).ok_or_else(|| TestError::Syntax {
+                    line,
+                    message: format!("unknown variable: {}", variable)
+                })?;
+                let value = self.word("a value")?;
+                let value = parse_value(&value).ok_or_else(|| TestError::Syntax {
+                    line,
+                    message: format!("illegal value: {}", value)
+                })?;
+                Command::Set(variable, value)
+            }
+            "repeat" => {
+                let count = match self.peek() {
+                    Some(Token::Word(_)) => {
+                        let count = self.word("a repeat count")?;
+                        count.parse().or_else(|_| syntax_error(line, "expected a repeat count"))?
+                    }
+                    _ => u64::MAX
+                };
+                return Ok(Statement { command: Command::Repeat(count, self.block()?), line });
+            }
+            "while" => {
+                let variable = self.word("a variable")?;
+                let variable = parse_variable(&variable).ok_or_else(|| TestError::Syntax {
+                    line,
+                    message: format!("unknown variable: {}", variable)
+                })?;
+                let comparison = match self.word("a comparison")?.as_str() {
+                    "=" => Comparison::Eq,
+                    "<>" => Comparison::Ne,
+                    "<" => Comparison::Lt,
+                    "<=" => Comparison::Le,
+                    ">" => Comparison::Gt,
+                    ">=" => Comparison::Ge,
+                    other => return syntax_error(line, format!("illegal comparison: {}", other))
+                };
+                let value = self.word("a value")?;
+                let value = parse_value(&value).ok_or_else(|| TestError::Syntax {
+                    line,
+                    message: format!("illegal value: {}", value)
+                })?;
+                let body = self.block()?;
+                return Ok(Statement { command: Command::While(variable, comparison, value, body), line });
+            }
+            "tick" => Command::Tick,
+            "tock" => Command::Tock,
+            "ticktock" => Command::TickTock,
+            "output" => Command::Output,
+            "echo" => match self.next() {
+                Some(Token::Str(text)) | Some(Token::Word(text)) => Command::Echo(text),
+                _ => return syntax_error(line, "expected a string")
+            },
+            "clear-echo" => Command::ClearEcho,
+            other => return syntax_error(line, format!("unknown command: {}", other))
+        };
+        self.end_of_command()?;
+        Ok(Statement { command, line })
+    }
+}
+
+fn parse_variable(name: &str) -> Option<Variable> {
+    let index = |prefix: &str| name.strip_prefix(prefix)?.strip_suffix(']')?.parse().ok();
+    match name {
+        "A" => Some(Variable::A),
+        "D" => Some(Variable::D),
+        "PC" => Some(Variable::Pc),
+        "time" => Some(Variable::Time),
+        _ => index("RAM[").map(Variable::Ram).or_else(|| index("ROM[").map(Variable::Rom))
+    }
+}
+
+fn parse_value(value: &str) -> Option<i16> {
+    let parse_radix = |digits: &str, radix| u16::from_str_radix(digits, radix).ok().map(|v| v as i16);
+    if let Some(digits) = value.strip_prefix("%X") {
+        parse_radix(digits, 16)
+    } else if let Some(digits) = value.strip_prefix("%B") {
+        parse_radix(digits, 2)
+    } else {
+        value.strip_prefix("%D").unwrap_or(value).parse().ok()
+    }
+}
+
+fn parse_column(spec: &str) -> Option<Column> {
+    let (name, format) = match spec.find('%') {
+        Some(i) => (&spec[..i], &spec[i + 1..]),
+        None => (spec, "D1.6.1")
+    };
+    let variable = parse_variable(name)?;
+    let mut chars = format.chars();
+    let format_char = chars.next().filter(|c| "DXBS".contains(*c))?;
+    let sizes: Vec<usize> = chars.as_str().split('.')
+        .map(|n| n.parse().ok())
+        .collect::<Option<_>>()?;
+    if sizes.len() != 3 {
+        return None;
+    }
+    Some(Column {
+        name: name.to_owned(),
+        variable,
+        format: format_char,
+        pad_left: sizes[0],
+        len: sizes[1],
+        pad_right: sizes[2]
+    })
+}
+
+impl Column {
+    fn header(&self) -> String {
+        let space = self.pad_left + self.len + self.pad_right;
+        let name: String = self.name.chars().take(space).collect();
+        let left = (space - name.len()) / 2;
+        let right = space - left - name.len();
+        format!("{}{}{}", " ".repeat(left), name, " ".repeat(right))
+    }
+
+    fn cell(&self, value: i64) -> String {
+        let text = match self.format {
+            'X' => format!("{:04X}", value as u16),
+            'B' => format!("{:016b}", value as u16),
+            _ => value.to_string()
+        };
+        let text = if text.len() > self.len {
+            text[text.len() - self.len..].to_owned()
+        } else {
+            format!("{:>width$}", text, width = self.len)
+        };
+        format!("{}{}{}", " ".repeat(self.pad_left), text, " ".repeat(self.pad_right))
+    }
+}
+
+struct TestRunner<E: FnMut(&str)> {
+    dir: PathBuf,
+    computer: Computer,
+    output_list: Vec<Column>,
+    output: Option<(PathBuf, File)>,
+    compare: Option<Vec<String>>,
+    output_lines: usize,
+    echo: E
+}
+
+impl<E: FnMut(&str)> TestRunner<E> {
+    fn resolve(&self, file: &str) -> PathBuf {
+        self.dir.join(file)
+    }
+
+    fn read(&self, value: Variable) -> i64 {
+        let computer = &self.computer;
+        match value {
+            Variable::A => computer.a_register as i64,
+            Variable::D => computer.d_register as i64,
+            Variable::Pc => computer.pc as i64,
+            Variable::Time => computer.cycles as i64,
+            Variable::Ram(i) => computer.memory.get(i).copied().unwrap_or(0) as i64,
+            Variable::Rom(i) => computer.rom.get(i).copied().flatten().unwrap_or(0) as i64
+        }
+    }
+
+    fn write_line(&mut self, text: String) -> Result<(), TestError> {
+        self.output_lines += 1;
+        if let Some((path, output)) = &mut self.output {
+            writeln!(output, "{}", text).map_err(|error| TestError::Io {
+                path: path.clone(),
+                error
+            })?;
+        }
+        if let Some(compare) = &self.compare {
+            let expected = compare.get(self.output_lines - 1).map(|l| l.trim_end());
+            if expected != Some(text.trim_end()) {
+                return Err(TestError::ComparisonFailure { line: self.output_lines });
+            }
+        }
+        Ok(())
+    }
+
+    fn format_row(&self, cells: impl Iterator<Item = String>) -> String {
+        let cells: Vec<String> = cells.collect();
+        format!("|{}|", cells.join("|"))
+    }
+
+    fn execute(&mut self, statements: &[Statement]) -> Result<(), TestError> {
+        for statement in statements {
+            let line = statement.line;
+            match &statement.command {
+                Command::Load(file) => {
+                    let program = load_program(&self.resolve(file)).map_err(|error| TestError::Runtime {
+                        line,
+                        message: error.to_string()
+                    })?;
+                    self.computer = Computer::new();
+                    self.computer.set_history_size(0);
+                    self.computer.trap_empty_rom = false;
+                    self.computer.load(&program).map_err(|error| TestError::Runtime {
+                        line,
+                        message: error.to_string()
+                    })?;
+                }
+                Command::OutputFile(file) => {
+                    let path = self.resolve(file);
+                    match File::create(&path) {
+                        Ok(output) => self.output = Some((path, output)),
+                        Err(error) => return Err(TestError::Io { path, error })
+                    }
+                }
+                Command::CompareTo(file) => {
+                    let path = self.resolve(file);
+                    let lines = lines_from_file(&path).map_err(|error| TestError::Io { path, error })?;
+                    self.compare = Some(lines);
+                }
+                Command::OutputList(columns) => {
+                    let header = self.format_row(columns.iter().map(Column::header));
+                    self.output_list = columns.clone();
+                    self.write_line(header)?;
+                }
+                Command::Set(variable, value) => {
+                    let computer = &mut self.computer;
+                    match *variable {
+                        Variable::A => computer.a_register = *value,
+                        Variable::D => computer.d_register = *value,
+                        Variable::Pc => computer.pc = *value,
+                        Variable::Ram(i) if i < computer.memory.len() => computer.memory[i] = *value,
+                        Variable::Rom(i) if i < computer.rom.len() => computer.rom[i] = Some(*value),
+                        _ => return Err(TestError::Runtime {
+                            line,
+                            message: "illegal variable to set".to_owned()
+                        })
+                    }
+                }
+                Command::Repeat(count, body) => {
+                    for _ in 0..*count {
+                        self.execute(body)?;
+                    }
+                }
+                Command::While(variable, comparison, value, body) => {
+                    while compare(self.read(*variable), *comparison, *value as i64) {
+                        self.execute(body)?;
+                    }
+                }
+                Command::Tick => {}
+                Command::Tock | Command::TickTock => {
+                    self.computer.step().map_err(|fault| TestError::Runtime {
+                        line,
+                        message: fault.to_string()
+                    })?;
+                }
+                Command::Output => {
+                    let row = self.format_row(self.output_list.iter().map(|c| c.cell(self.read(c.variable))));
+                    self.write_line(row)?;
+                }
+                Command::Echo(text) => (self.echo)(text),
+                Command::ClearEcho => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn compare(left: i64, comparison: Comparison, right: i64) -> bool {
+    match comparison {
+        Comparison::Eq => left == right,
+        Comparison::Ne => left != right,
+        Comparison::Lt => left < right,
+        Comparison::Le => left <= right,
+        Comparison::Gt => left > right,
+        Comparison::Ge => left >= right
+    }
+}
+
+/// Runs a CPUEmulator `.tst` script. Files referenced by the script are
+/// resolved relative to the script's directory. The text of `echo`
+/// commands is passed to `echo`.
+pub fn run_test_script(path: &Path, echo: impl FnMut(&str)) -> Result<(), TestError> {
+    let input = lines_from_file(path).map_err(|error| TestError::Io { path: path.to_owned(), error })?;
+    let mut parser = Parser { tokens: lex(&input), position: 0 };
+    let statements = parser.statements()?;
+
+    let mut runner = TestRunner {
+        dir: path.parent().map(Path::to_owned).unwrap_or_default(),
+        computer: Computer::new(),
+        output_list: vec![],
+        output: None,
+        compare: None,
+        output_lines: 0,
+        echo
+    };
+    runner.computer.set_history_size(0);
+    runner.execute(&statements)
+}
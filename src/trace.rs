@@ -0,0 +1,200 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+use crate::computer::{Computer, Fault, MemoryAccess};
+use crate::disassembler::to_asm;
+
+/// Identifies a binary trace, followed by a version byte
+const MAGIC: &[u8; 4] = b"HTRC";
+const VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One readable line per instruction
+    Text,
+    /// Fixed-size little-endian records, readable with `TraceReader`
+    Binary
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TraceFormat, String> {
+        match s {
+            "text" => Ok(TraceFormat::Text),
+            "bin" | "binary" => Ok(TraceFormat::Binary),
+            _ => Err(format!("unknown trace format `{}` (expected text or bin)", s))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub address: u16,
+    pub old: i16,
+    pub new: i16
+}
+
+/// One executed instruction and the state it left behind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// The cycle the instruction was executed in, starting at 0
+    pub cycle: u64,
+    pub pc: u16,
+    pub instruction: i16,
+    pub a_register: i16,
+    pub d_register: i16,
+    pub write: Option<MemoryWrite>
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut line = format!(
+            "{:>8} {:>5}  {:<16} A={:<6} D={:<6}",
+            self.cycle,
+            self.pc,
+            to_asm(self.instruction),
+            self.a_register,
+            self.d_register
+        );
+        if let Some(write) = self.write {
+            line.push_str(&format!(" RAM[{}]: {} -> {}", write.address, write.old, write.new));
+        }
+        write!(f, "{}", line.trim_end())
+    }
+}
+
+/// Executes one instruction with `Computer::step` and describes it.
+pub fn step(computer: &mut Computer) -> Result<(TraceEntry, MemoryAccess), Fault> {
+    let cycle = computer.cycles;
+    let pc = computer.pc as u16;
+    let instruction = computer.rom.get(pc as usize).copied().flatten().unwrap_or(0);
+    // A write always goes to the address in A before the instruction
+    let old = computer.memory.get(computer.a_register as u16 as usize).copied();
+
+    let access = computer.step()?;
+    let entry = TraceEntry {
+        cycle,
+        pc,
+        instruction,
+        a_register: computer.a_register,
+        d_register: computer.d_register,
+        write: access.write.map(|(address, new)| MemoryWrite { address, old: old.unwrap_or(0), new })
+    };
+    Ok((entry, access))
+}
+
+/// Records every instruction executed through `step`. Write errors don't
+/// interrupt execution; the first one is returned by `finish`.
+pub struct TraceWriter<W: Write> {
+    out: W,
+    format: TraceFormat,
+    error: Option<io::Error>
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(mut out: W, format: TraceFormat) -> io::Result<TraceWriter<W>> {
+        if format == TraceFormat::Binary {
+            out.write_all(MAGIC)?;
+            out.write_all(&[VERSION])?;
+        }
+        Ok(TraceWriter { out, format, error: None })
+    }
+
+    /// Executes one instruction with `Computer::step` and records it.
+    /// Faulting instructions aren't executed, so they aren't recorded.
+    pub fn step(&mut self, computer: &mut Computer) -> Result<MemoryAccess, Fault> {
+        let (entry, access) = step(computer)?;
+        if self.error.is_none() {
+            self.error = self.write_entry(&entry).err();
+        }
+        Ok(access)
+    }
+
+    pub fn write_entry(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        match self.format {
+            TraceFormat::Text => writeln!(self.out, "{}", entry),
+            TraceFormat::Binary => {
+                let mut record = Vec::with_capacity(23);
+                record.extend_from_slice(&entry.cycle.to_le_bytes());
+                record.extend_from_slice(&entry.pc.to_le_bytes());
+                record.extend_from_slice(&entry.instruction.to_le_bytes());
+                record.extend_from_slice(&entry.a_register.to_le_bytes());
+                record.extend_from_slice(&entry.d_register.to_le_bytes());
+                match entry.write {
+                    Some(write) => {
+                        record.push(1);
+                        record.extend_from_slice(&write.address.to_le_bytes());
+                        record.extend_from_slice(&write.old.to_le_bytes());
+                        record.extend_from_slice(&write.new.to_le_bytes());
+                    }
+                    None => record.push(0)
+                }
+                self.out.write_all(&record)
+            }
+        }
+    }
+
+    /// Flushes the trace and reports the first error writing it.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.out.flush()
+        }
+    }
+}
+
+/// Reads the entries of a binary trace.
+pub struct TraceReader<R: Read> {
+    input: R
+}
+
+impl<R: Read> TraceReader<R> {
+    pub fn new(mut input: R) -> io::Result<TraceReader<R>> {
+        let mut header = [0; 5];
+        input.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a binary trace"));
+        }
+        Ok(TraceReader { input })
+    }
+
+    fn read_entry(&mut self) -> io::Result<Option<TraceEntry>> {
+        let mut record = [0; 17];
+        // A clean end of file can only fall between records
+        match self.input.read(&mut record[..1])? {
+            0 => return Ok(None),
+            _ => self.input.read_exact(&mut record[1..])?
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([record[i], record[i + 1]]);
+        let mut cycle = [0; 8];
+        cycle.copy_from_slice(&record[..8]);
+        let write = match record[16] {
+            0 => None,
+            1 => {
+                let mut write = [0; 6];
+                self.input.read_exact(&mut write)?;
+                let i16_at = |i: usize| i16::from_le_bytes([write[i], write[i + 1]]);
+                Some(MemoryWrite { address: i16_at(0) as u16, old: i16_at(2), new: i16_at(4) })
+            }
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "corrupt trace record"))
+        };
+        Ok(Some(TraceEntry {
+            cycle: u64::from_le_bytes(cycle),
+            pc: u16_at(8),
+            instruction: u16_at(10) as i16,
+            a_register: u16_at(12) as i16,
+            d_register: u16_at(14) as i16,
+            write
+        }))
+    }
+}
+
+impl<R: Read> Iterator for TraceReader<R> {
+    type Item = io::Result<TraceEntry>;
+
+    fn next(&mut self) -> Option<io::Result<TraceEntry>> {
+        self.read_entry().transpose()
+    }
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::computer::{MemoryAccess, RAM_SIZE};
+use crate::format::NumberFormat;
+
+/// The accesses a watchpoint stops on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    ReadWrite
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge
+}
+
+impl Comparison {
+    pub fn compare(self, left: i16, right: i16) -> bool {
+        match self {
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">="
+        }
+    }
+}
+
+/// Pauses execution when an instruction reads or writes a range of RAM,
+/// optionally only when the value read or written passes a comparison.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub kind: AccessKind,
+    pub addresses: Range<usize>,
+    pub condition: Option<(Comparison, i16)>
+}
+
+/// An access that triggered a watchpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    /// Either `Read` or `Write`
+    pub kind: AccessKind,
+    pub address: u16,
+    pub value: i16
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            AccessKind::Read => write!(f, "read {} from RAM[{}]", self.value, self.address),
+            _ => write!(f, "wrote {} to RAM[{}]", self.value, self.address)
+        }
+    }
+}
+
+impl Watchpoint {
+    /// Parses `[r|w|rw] <address>[..<end>] [<comparison> <value>]`, e.g.
+    /// `256`, `rw 256..260` or `w sum < 0`. The kind defaults to `w` and the
+    /// end of a range is exclusive. Addresses and values are decimal or `0x`
+    /// hex; `symbol` resolves any other address.
+    pub fn parse(spec: &str, symbol: impl Fn(&str) -> Option<u16>) -> Result<Watchpoint, String> {
+        let spec = spec.trim();
+        let (kind, rest) = match spec.split_once(char::is_whitespace) {
+            Some(("r", rest)) => (AccessKind::Read, rest),
+            Some(("w", rest)) => (AccessKind::Write, rest),
+            Some(("rw", rest)) => (AccessKind::ReadWrite, rest),
+            _ => (AccessKind::Write, spec)
+        };
+
+        let (location, condition) = match rest.find(|c| "=!<>".contains(c)) {
+            Some(i) => (&rest[..i], Some(parse_condition(&rest[i..])?)),
+            None => (rest, None)
+        };
+        let address = |text: &str| {
+            let text = text.trim();
+            parse_number(text)
+                .filter(|&address| address >= 0)
+                .map(|address| address as usize)
+                .or_else(|| symbol(text).map(|address| address as usize))
+                .ok_or_else(|| format!("invalid address: {}", text))
+        };
+        let addresses = match location.split_once("..") {
+            Some((start, end)) => address(start)?..address(end)?,
+            None => {
+                let address = address(location)?;
+                address..address + 1
+            }
+        };
+        if addresses.start >= addresses.end || addresses.end > RAM_SIZE {
+            return Err(format!("invalid address range: {}", location.trim()));
+        }
+
+        Ok(Watchpoint { kind, addresses, condition })
+    }
+
+    /// Returns the access that triggers this watchpoint, if any. Writes are
+    /// reported before reads.
+    pub fn check(&self, access: &MemoryAccess) -> Option<WatchHit> {
+        let write = access.write.map(|access| (AccessKind::Write, access));
+        let read = access.read.map(|access| (AccessKind::Read, access));
+        write.into_iter().chain(read)
+            .filter(|(kind, _)| self.kind == AccessKind::ReadWrite || self.kind == *kind)
+            .find(|(_, (address, value))| {
+                let passes = match self.condition {
+                    Some((comparison, right)) => comparison.compare(*value, right),
+                    None => true
+                };
+                self.addresses.contains(&(*address as usize)) && passes
+            })
+            .map(|(kind, (address, value))| WatchHit { kind, address, value })
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            AccessKind::Read => "r",
+            AccessKind::Write => "w",
+            AccessKind::ReadWrite => "rw"
+        };
+        write!(f, "{} RAM[{}", kind, self.addresses.start)?;
+        if self.addresses.len() > 1 {
+            write!(f, "..{}", self.addresses.end)?;
+        }
+        write!(f, "]")?;
+        if let Some((comparison, value)) = self.condition {
+            write!(f, " {} {}", comparison.symbol(), value)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_condition(text: &str) -> Result<(Comparison, i16), String> {
+    let (comparison, value) = if let Some(value) = text.strip_prefix("==") {
+        (Comparison::Eq, value)
+    } else if let Some(value) = text.strip_prefix("!=").or_else(|| text.strip_prefix("<>")) {
+        (Comparison::Ne, value)
+    } else if let Some(value) = text.strip_prefix("<=") {
+        (Comparison::Le, value)
+    } else if let Some(value) = text.strip_prefix(">=") {
+        (Comparison::Ge, value)
+    } else if let Some(value) = text.strip_prefix('=') {
+        (Comparison::Eq, value)
+    } else if let Some(value) = text.strip_prefix('<') {
+        (Comparison::Lt, value)
+    } else if let Some(value) = text.strip_prefix('>') {
+        (Comparison::Gt, value)
+    } else {
+        return Err(format!("invalid condition: {}", text));
+    };
+    let value = value.trim();
+    parse_number(value)
+        .filter(|&value| value >= i16::MIN as i32 && value <= u16::MAX as i32)
+        .map(|value| value as i16)
+        .map(|value| (comparison, value))
+        .ok_or_else(|| format!("invalid value: {}", value))
+}
+
+fn parse_number(text: &str) -> Option<i32> {
+    if text.starts_with("0x") {
+        NumberFormat::Hex.parse(text).map(|value| value as u16 as i32)
+    } else {
+        text.parse().ok()
+    }
+}
@@ -0,0 +1,117 @@
+use hack_cpu_emulator::assembler::assemble;
+use hack_cpu_emulator::computer::{alu, Computer};
+use hack_cpu_emulator::utils::get_bit_slice;
+
+type Expected = fn(i16, i16) -> i16;
+
+const SAMPLES: [i16; 10] = [0, 1, -1, 2, 17, -3, 12_345, -12_345, i16::MAX, i16::MIN];
+
+fn comp_bits(comp: &str) -> (bool, i16) {
+    let code = assemble(&[format!("D={}", comp)]).unwrap().code[0];
+    (get_bit_slice(code, 12, 13) == 1, get_bit_slice(code, 6, 12))
+}
+
+#[test]
+fn documented_comp_encodings_match_truth_table() {
+    let table: [(&str, Expected); 18] = [
+        ("0", |_, _| 0),
+        ("1", |_, _| 1),
+        ("-1", |_, _| -1),
+        ("D", |x, _| x),
+        ("A", |_, y| y),
+        ("!D", |x, _| !x),
+        ("!A", |_, y| !y),
+        ("-D", |x, _| x.wrapping_neg()),
+        ("-A", |_, y| y.wrapping_neg()),
+        ("D+1", |x, _| x.wrapping_add(1)),
+        ("A+1", |_, y| y.wrapping_add(1)),
+        ("D-1", |x, _| x.wrapping_sub(1)),
+        ("A-1", |_, y| y.wrapping_sub(1)),
+        ("D+A", |x, y| x.wrapping_add(y)),
+        ("D-A", |x, y| x.wrapping_sub(y)),
+        ("A-D", |x, y| y.wrapping_sub(x)),
+        ("D&A", |x, y| x & y),
+        ("D|A", |x, y| x | y)
+    ];
+
+    let mut encodings = 0;
+    for (comp, expected) in table.iter() {
+        let mut variants = vec![(comp.to_string(), false)];
+        if comp.contains('A') {
+            variants.push((comp.replace('A', "M"), true));
+        }
+        for (comp, uses_memory) in variants {
+            let (a_bit, bits) = comp_bits(&comp);
+            assert_eq!(a_bit, uses_memory, "a bit of {}", comp);
+            for &x in SAMPLES.iter() {
+                for &y in SAMPLES.iter() {
+                    let want = expected(x, y);
+                    assert_eq!(alu(x, y, bits), (want, want == 0, want < 0), "{} with x={} y={}", comp, x, y);
+                }
+            }
+            encodings += 1;
+        }
+    }
+    assert_eq!(encodings, 28);
+}
+
+#[test]
+fn undocumented_comp_encodings() {
+    let table: [(i16, Expected); 10] = [
+        (0b000001, |x, y| !(x & y)),
+        (0b000011, |x, y| !x.wrapping_add(y)),
+        (0b000110, |x, y| x.wrapping_sub(y).wrapping_sub(1)),
+        (0b001010, |x, _| x),
+        (0b100010, |_, y| y),
+        (0b010100, |x, y| !x & !y),
+        (0b101000, |_, _| 0),
+        (0b101011, |_, _| -1),
+        (0b111110, |_, _| -2),
+        (0b111000, |_, _| 0)
+    ];
+
+    for (bits, expected) in table.iter() {
+        for &x in SAMPLES.iter() {
+            for &y in SAMPLES.iter() {
+                let want = expected(x, y);
+                assert_eq!(alu(x, y, *bits), (want, want == 0, want < 0), "{:06b} with x={} y={}", bits, x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn every_control_pattern_follows_the_pipeline() {
+    for bits in 0..64i16 {
+        for &x in SAMPLES.iter() {
+            for &y in SAMPLES.iter() {
+                // Model the chip on unsigned words so the adder can't overflow
+                let zx = if bits & 0b100000 != 0 { 0 } else { x as u16 };
+                let nx = if bits & 0b010000 != 0 { !zx } else { zx };
+                let zy = if bits & 0b001000 != 0 { 0 } else { y as u16 };
+                let ny = if bits & 0b000100 != 0 { !zy } else { zy };
+                let f = if bits & 0b000010 != 0 { nx.wrapping_add(ny) } else { nx & ny };
+                let out = if bits & 0b000001 != 0 { !f } else { f } as i16;
+                assert_eq!(alu(x, y, bits), (out, out == 0, out < 0), "{:06b} with x={} y={}", bits, x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn addition_wraps_instead_of_overflowing() {
+    let program = assemble(&[
+        "@32767".to_owned(),
+        "D=A".to_owned(),
+        "D=D+1".to_owned(),
+        "@0".to_owned(),
+        "M=D".to_owned()
+    ]).unwrap();
+    let mut computer = Computer::new();
+    computer.load(&program.code).unwrap();
+    for _ in 0..program.code.len() {
+        computer.step().unwrap();
+    }
+    assert_eq!(computer.d_register, i16::MIN);
+    assert_eq!(computer.memory[0], i16::MIN);
+}
@@ -0,0 +1,236 @@
+use hack_cpu_emulator::assembler::{assemble, AssemblyError, ErrorKind};
+
+fn source(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|line| line.to_string()).collect()
+}
+
+/// Assembles a program with exactly one error and renders it.
+fn single_error(lines: &[&str]) -> (AssemblyError, String) {
+    let source = source(lines);
+    let mut errors = assemble(&source).unwrap_err();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    let error = errors.remove(0);
+    let rendered = error.render("Prog.asm", &source);
+    (error, rendered)
+}
+
+fn assert_position(error: &AssemblyError, kind: ErrorKind, line: usize, column: usize, token: &str) {
+    assert_eq!((error.kind, error.line, error.column, error.token.as_str()), (kind, line, column, token));
+}
+
+#[test]
+fn unterminated_label() {
+    let (error, rendered) = single_error(&["@LOOP", "  (LOOP", "0;JMP"]);
+    assert_position(&error, ErrorKind::MalformedLabel, 2, 3, "(LOOP");
+    assert_eq!(rendered, "\
+error: malformed label `(LOOP`
+ --> Prog.asm:2:3
+  |
+2 |   (LOOP
+  |   ^^^^^
+  = expected one of: (LABEL)");
+}
+
+#[test]
+fn caret_under_tab_indented_token() {
+    let (error, rendered) = single_error(&["\t@1", "\tD=D*2"]);
+    assert_position(&error, ErrorKind::InvalidComp, 2, 4, "D*2");
+    assert!(rendered.starts_with("\
+error: invalid comp `D*2`
+ --> Prog.asm:2:4
+  |
+2 | \tD=D*2
+  | \t  ^^^"));
+}
+
+#[test]
+fn empty_address() {
+    let (error, rendered) = single_error(&["D=A", "@ // nothing"]);
+    assert_position(&error, ErrorKind::MissingAddress, 2, 1, "@");
+    assert_eq!(rendered, "\
+error: missing address after `@`
+ --> Prog.asm:2:1
+  |
+2 | @ // nothing
+  | ^");
+}
+
+#[test]
+fn invalid_dest_comp_and_jump() {
+    let (error, rendered) = single_error(&["  X=D+1"]);
+    assert_position(&error, ErrorKind::InvalidDest, 1, 3, "X");
+    assert_eq!(rendered, "\
+error: invalid dest `X`
+ --> Prog.asm:1:3
+  |
+1 |   X=D+1
+  |   ^
+  = expected one of: M, D, MD, A, AM, AD, AMD");
+
+    let (error, rendered) = single_error(&["D=D*2;JMP"]);
+    assert_position(&error, ErrorKind::InvalidComp, 1, 3, "D*2");
+    assert!(rendered.starts_with("\
+error: invalid comp `D*2`
+ --> Prog.asm:1:3
+  |
+1 | D=D*2;JMP
+  |   ^^^
+  = expected one of: 0, 1, -1, D, A, M, !D,"));
+
+    let (error, rendered) = single_error(&["0;JMPX"]);
+    assert_position(&error, ErrorKind::InvalidJump, 1, 3, "JMPX");
+    assert_eq!(rendered, "\
+error: invalid jump `JMPX`
+ --> Prog.asm:1:3
+  |
+1 | 0;JMPX
+  |   ^^^^
+  = expected one of: JGT, JEQ, JGE, JLT, JNE, JLE, JMP");
+}
+
+#[test]
+fn constant_out_of_range() {
+    let (error, rendered) = single_error(&["@32767", "@32768"]);
+    assert_position(&error, ErrorKind::AddressOutOfRange, 2, 2, "32768");
+    assert_eq!(rendered, "\
+error: constant `32768` is out of range
+ --> Prog.asm:2:2
+  |
+2 | @32768
+  |  ^^^^^
+  = expected one of: 0..=32767");
+}
+
+#[test]
+fn duplicate_label() {
+    let lines = ["(LOOP)", "@LOOP", "0;JMP", "", "", "", "", "", "", "(LOOP)"];
+    let (error, rendered) = single_error(&lines);
+    assert_position(&error, ErrorKind::DuplicateLabel, 10, 2, "LOOP");
+    // The gutter widens with the line number
+    assert_eq!(rendered, "\
+error: label `LOOP` is already defined
+  --> Prog.asm:10:2
+   |
+10 | (LOOP)
+   |  ^^^^");
+}
+
+#[test]
+fn every_error_is_reported() {
+    let errors = assemble(&source(&["X=1", "@", "0;JMPX"])).unwrap_err();
+    let kinds: Vec<(ErrorKind, usize)> = errors.iter().map(|error| (error.kind, error.line)).collect();
+    assert_eq!(kinds, vec![(ErrorKind::InvalidDest, 1), (ErrorKind::MissingAddress, 2), (ErrorKind::InvalidJump, 3)]);
+    assert_eq!(errors[1].to_string(), "2:1: missing address after `@`");
+}
+
+const COUNTDOWN: [&str; 13] = [
+    "// Counts down from 3", "@3", "D=A", "@count", "M=D",
+    "(LOOP)", "  @count", "  MD=M-1 // decrement", "  @LOOP", "  D;JGT",
+    "(END)", "@END", "0;JMP"
+];
+
+#[test]
+fn listing() {
+    let source = source(&COUNTDOWN);
+    // Written without line continuations, which would strip the leading spaces
+    assert_eq!(assemble(&source).unwrap().listing(&source), "                         // Counts down from 3
+    0  0000000000000011  @3
+    1  1110110000010000  D=A
+    2  0000000000010000  @count
+    3  1110001100001000  M=D
+                         (LOOP)
+    4  0000000000010000    @count
+    5  1111110010011000    MD=M-1 // decrement
+    6  0000000000000100    @LOOP
+    7  1110001100000001    D;JGT
+                         (END)
+    8  0000000000001000  @END
+    9  1110101010000111  0;JMP
+");
+}
+
+#[test]
+fn symbol_dump() {
+    assert_eq!(assemble(&source(&COUNTDOWN)).unwrap().symbol_dump(), "\
+R0                           0  predefined
+SP                           0  predefined
+LCL                          1  predefined
+R1                           1  predefined
+ARG                          2  predefined
+R2                           2  predefined
+R3                           3  predefined
+THIS                         3  predefined
+R4                           4  predefined
+THAT                         4  predefined
+R5                           5  predefined
+R6                           6  predefined
+R7                           7  predefined
+R8                           8  predefined
+R9                           9  predefined
+R10                         10  predefined
+R11                         11  predefined
+R12                         12  predefined
+R13                         13  predefined
+R14                         14  predefined
+R15                         15  predefined
+SCREEN                   16384  predefined
+KBD                      24576  predefined
+LOOP                         4  label
+END                          8  label
+count                       16  variable
+");
+}
+
+#[test]
+fn source_map() {
+    assert_eq!(assemble(&source(&COUNTDOWN)).unwrap().source_map, vec![1, 2, 3, 4, 6, 7, 8, 9, 11, 12]);
+
+    // Blank lines, comments and labels don't take an address
+    let assembly = assemble(&source(&[
+        "",
+        "// Setup",
+        "   @2 // two",
+        "",
+        "\t",
+        "(START)",
+        "(AGAIN)",
+        "D=A",
+        "   // done",
+        "@START",
+        ""
+    ])).unwrap();
+    assert_eq!(assembly.source_map, vec![2, 7, 9]);
+    assert_eq!(assembly.source_map.len(), assembly.code.len());
+}
+
+#[test]
+fn program_too_large_for_rom() {
+    let lines = vec!["D=D+1".to_owned(); 70_000];
+    let errors = assemble(&lines).unwrap_err();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_position(&errors[0], ErrorKind::ProgramTooLarge, 32_769, 1, "D=D+1");
+    assert_eq!(errors[0].message(), "`D=D+1` does not fit in the 32768 words of ROM");
+
+    // A full ROM is fine
+    assert_eq!(assemble(&lines[..32_768]).unwrap().code.len(), 32_768);
+}
+
+#[test]
+fn symbols_out_of_range() {
+    // A label past the last ROM address can't be loaded into A
+    let mut lines = vec!["D=D+1".to_owned(); 32_768];
+    lines.push("(END)".to_owned());
+    let error = assemble(&lines).unwrap_err().remove(0);
+    assert_position(&error, ErrorKind::SymbolOutOfRange, 32_769, 2, "END");
+    assert_eq!(error.message(), "the address of `END` is out of range");
+
+    // Variables are allocated from 16 up to 32767
+    let mut lines: Vec<String> = (16..=32_767).map(|i| format!("@v{}", i)).collect();
+    let assembly = assemble(&lines).unwrap();
+    assert_eq!(assembly.code.last(), Some(&32_767));
+    lines.push("@overflow".to_owned());
+    lines.push("@v16".to_owned());
+    let errors = assemble(&lines).unwrap_err();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_position(&errors[0], ErrorKind::SymbolOutOfRange, 32_753, 2, "overflow");
+}
@@ -0,0 +1,32 @@
+use hack_cpu_emulator::computer::{Computer, ProgramTooLarge, ROM_SIZE};
+
+#[test]
+fn memory_matches_the_hack_platform() {
+    let computer = Computer::new();
+    assert_eq!(computer.rom.len(), 32_768);
+    assert_eq!(computer.memory.len(), 24_577);
+    assert!(computer.rom.iter().all(Option::is_none));
+}
+
+#[test]
+fn load_fills_rom_from_the_start() {
+    let mut computer = Computer::new();
+    assert_eq!(computer.load(&[1, 2, 3]), Ok(()));
+    assert_eq!(&computer.rom[..4], &[Some(1), Some(2), Some(3), None]);
+
+    let program = vec![7; ROM_SIZE];
+    assert_eq!(computer.load(&program), Ok(()));
+    assert_eq!(computer.rom[ROM_SIZE - 1], Some(7));
+}
+
+#[test]
+fn load_rejects_programs_larger_than_rom() {
+    let mut computer = Computer::new();
+    computer.load(&[1, 2, 3]).unwrap();
+
+    let error = computer.load(&vec![7; ROM_SIZE + 1]).unwrap_err();
+    assert_eq!(error, ProgramTooLarge { instructions: ROM_SIZE + 1 });
+    assert_eq!(error.to_string(), "program of 32769 instructions does not fit in the 32768 words of ROM");
+    // ROM is left as it was
+    assert_eq!(&computer.rom[..4], &[Some(1), Some(2), Some(3), None]);
+}
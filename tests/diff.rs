@@ -0,0 +1,72 @@
+use hack_cpu_emulator::assembler::assemble;
+use hack_cpu_emulator::computer::Fault;
+use hack_cpu_emulator::diff::{diff, DiffResult, Execution};
+
+fn execution(source: &[&str]) -> Execution {
+    let source: Vec<String> = source.iter().map(|line| line.to_string()).collect();
+    Execution::new(&assemble(&source).unwrap().code).unwrap()
+}
+
+const COUNT: [&str; 10] = ["@3", "D=A", "@16", "M=D", "@16", "M=M-1", "D=M", "@4", "D;JGT", "(END)"];
+
+fn count(mut source: Vec<&'static str>) -> Execution {
+    source.extend_from_slice(&["@END", "0;JMP"]);
+    execution(&source)
+}
+
+#[test]
+fn identical_programs() {
+    let result = diff(count(COUNT.to_vec()), count(COUNT.to_vec()), 5);
+    assert_eq!(result, DiffResult::Identical { cycles: 19 });
+
+    // Different encodings with the same effect don't count as differences
+    let mut same = COUNT.to_vec();
+    same[1] = "AD=A";
+    assert_eq!(diff(count(COUNT.to_vec()), count(same), 5), DiffResult::Identical { cycles: 19 });
+}
+
+#[test]
+fn first_divergence() {
+    let mut buggy = COUNT.to_vec();
+    buggy[5] = "M=M+1";
+    let divergence = match diff(count(COUNT.to_vec()), count(buggy), 2) {
+        DiffResult::Diverged(divergence) => divergence,
+        result => panic!("unexpected {:?}", result)
+    };
+    assert_eq!(divergence.cycle, 5);
+    assert_eq!(divergence.context.iter().map(|entry| entry.pc).collect::<Vec<_>>(), vec![3, 4]);
+    assert_eq!(divergence.left.unwrap().unwrap().write.unwrap().new, 2);
+    assert_eq!(divergence.right.unwrap().unwrap().write.unwrap().new, 4);
+
+    let mut other = COUNT.to_vec();
+    other[0] = "@2";
+    let report = match diff(count(COUNT.to_vec()), count(other), 1) {
+        DiffResult::Diverged(divergence) => divergence.to_string(),
+        result => panic!("unexpected {:?}", result)
+    };
+    assert_eq!(report, "\
+first difference after 0 cycles in A
+<        0     0  @3               A=3      D=0
+>        0     0  @2               A=2      D=0
+");
+
+    // A program that halts early diverges from one that keeps going
+    let mut short = COUNT[..4].to_vec();
+    short.push("(END)");
+    match diff(count(COUNT.to_vec()), count(short), 1) {
+        DiffResult::Diverged(divergence) => assert!(divergence.to_string().ends_with("> (ended)\n")),
+        result => panic!("unexpected {:?}", result)
+    }
+}
+
+#[test]
+fn halts_and_faults() {
+    let divergence = match diff(count(COUNT.to_vec()), execution(&["@3", "D=A"]), 0) {
+        DiffResult::Diverged(divergence) => divergence,
+        result => panic!("unexpected {:?}", result)
+    };
+    assert_eq!(divergence.cycle, 2);
+    assert!(divergence.context.is_empty());
+    assert_eq!(divergence.right, Some(Err(Fault::EmptyRom(2))));
+    assert!(divergence.to_string().ends_with("> error: executing empty ROM at address 2\n"));
+}
@@ -0,0 +1,30 @@
+use hack_cpu_emulator::assembler::assemble;
+use hack_cpu_emulator::disassembler::{disassemble, to_asm, Syntax};
+
+#[test]
+fn every_word_disassembles() {
+    for word in i16::MIN..=i16::MAX {
+        let asm = to_asm(word);
+        disassemble(word, Syntax::ControlBits);
+
+        // Whatever has a mnemonic must assemble back to the canonical encoding
+        if !asm.contains("comp(") {
+            let canonical = if word < 0 { word | 0b0110_0000_0000_0000 } else { word };
+            assert_eq!(assemble(std::slice::from_ref(&asm)).unwrap().code, vec![canonical], "{}", asm);
+        }
+    }
+}
+
+#[test]
+fn unknown_comp_codes_show_raw_bits() {
+    assert_eq!(to_asm(0b1110_0000_0101_0000u16 as i16), "D=comp(0000001)");
+    assert_eq!(to_asm(0b1111_1111_1100_0111u16 as i16), "comp(1111111);JMP");
+}
+
+#[test]
+fn control_bits_syntax() {
+    let m_plus_one = assemble(&["D=M+1".to_owned()]).unwrap().code[0];
+    assert_eq!(disassemble(m_plus_one, Syntax::ControlBits), "D={a zx nx ny f no}");
+    let and = assemble(&["AM=D&A;JNE".to_owned()]).unwrap().code[0];
+    assert_eq!(disassemble(and, Syntax::ControlBits), "AM={};JNE");
+}
@@ -0,0 +1,81 @@
+use hack_cpu_emulator::computer::Computer;
+use hack_cpu_emulator::expression::Expression;
+
+fn symbol(name: &str) -> Option<u16> {
+    match name {
+        "i" => Some(16),
+        "LOOP" => Some(4),
+        "m" => Some(17),
+        _ => None
+    }
+}
+
+fn evaluate(text: &str, computer: &Computer) -> Option<i64> {
+    Expression::parse(text, symbol).unwrap().evaluate(computer)
+}
+
+#[test]
+fn registers_memory_and_symbols() {
+    let mut computer = Computer::new();
+    computer.pc = 42;
+    computer.a_register = 16;
+    computer.d_register = -3;
+    computer.memory[0] = 101;
+    computer.memory[16] = 10;
+
+    assert_eq!(evaluate("pc == 42 && RAM[0] > 100", &computer), Some(1));
+    assert_eq!(evaluate("D < 0", &computer), Some(1));
+    assert_eq!(evaluate("RAM[i] == 10", &computer), Some(1));
+    assert_eq!(evaluate("M = RAM[A]", &computer), Some(1));
+    assert_eq!(evaluate("PC == LOOP", &computer), Some(0));
+    assert_eq!(evaluate("RAM[i - 16] + -D", &computer), Some(104));
+    assert_eq!(evaluate("RAM[0xFFFF]", &computer), None);
+}
+
+#[test]
+fn precedence() {
+    let computer = Computer::new();
+    assert_eq!(evaluate("1 || 0 && 0", &computer), Some(1));
+    assert_eq!(evaluate("(1 || 0) && 0", &computer), Some(0));
+    assert_eq!(evaluate("1 + 2 == 3", &computer), Some(1));
+    assert_eq!(evaluate("!0 + 1", &computer), Some(2));
+    assert_eq!(evaluate("0x10 - 1 - 1", &computer), Some(14));
+}
+
+#[test]
+fn decimal_and_hex_literals_wrap_alike() {
+    let mut computer = Computer::new();
+    computer.memory[0] = -1;
+    computer.memory[1] = i16::MIN;
+
+    assert_eq!(evaluate("RAM[0] == 65535", &computer), Some(1));
+    assert_eq!(evaluate("RAM[0] == 0xFFFF", &computer), Some(1));
+    assert_eq!(evaluate("RAM[0] == -1", &computer), Some(1));
+    assert_eq!(evaluate("RAM[1] == 32768", &computer), Some(1));
+    assert_eq!(evaluate("RAM[1] == 0x8000", &computer), Some(1));
+    assert_eq!(evaluate("32767", &computer), Some(32767));
+    assert_eq!(evaluate("40000 < 0", &computer), Some(1));
+}
+
+#[test]
+fn parse_errors() {
+    assert!(Expression::parse("RAM[0", symbol).is_err());
+    assert!(Expression::parse("sum > 0", symbol).is_err());
+    assert!(Expression::parse("1 2", symbol).is_err());
+    assert!(Expression::parse("D ~ 1", symbol).is_err());
+    assert!(Expression::parse("", symbol).is_err());
+    assert!(Expression::parse("65536", symbol).is_err());
+    assert!(Expression::parse("99999999999999999999", symbol).is_err());
+}
+
+#[test]
+fn program_symbols_shadow_lowercase_registers() {
+    let mut computer = Computer::new();
+    computer.a_register = 5;
+    computer.memory[5] = 1;
+    computer.memory[17] = 2;
+
+    assert_eq!(evaluate("RAM[m]", &computer), Some(2));
+    assert_eq!(evaluate("M", &computer), Some(1));
+    assert_eq!(evaluate("a", &computer), Some(5));
+}
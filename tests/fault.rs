@@ -0,0 +1,103 @@
+use hack_cpu_emulator::assembler::assemble;
+use hack_cpu_emulator::computer::{Computer, Fault};
+
+fn load(lines: &[&str]) -> Computer {
+    let source: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    let mut computer = Computer::new();
+    computer.load(&assemble(&source).unwrap().code).unwrap();
+    computer
+}
+
+// Registers, program counter, cycle count and memory, to check a fault changes nothing
+fn state(computer: &Computer) -> (i16, i16, i16, u64, Vec<i16>) {
+    (computer.a_register, computer.d_register, computer.pc, computer.cycles, computer.memory.to_vec())
+}
+
+#[test]
+fn memory_access_past_ram_faults() {
+    // Writing, reading and reading-and-writing M all check the address
+    for instruction in ["M=1", "D=M", "M=M+1"] {
+        let mut computer = load(&["@7", "D=A", "@32767", instruction]);
+        for _ in 0..3 {
+            computer.step().unwrap();
+        }
+        let before = state(&computer);
+        assert_eq!(computer.step(), Err(Fault::InvalidAddress(32767)), "{}", instruction);
+        assert_eq!(state(&computer), before, "{}", instruction);
+    }
+
+    // The address is reported as unsigned
+    let mut computer = load(&["D=-1", "A=D", "M=0"]);
+    computer.step().unwrap();
+    computer.step().unwrap();
+    assert_eq!(computer.step(), Err(Fault::InvalidAddress(65535)));
+
+    // The keyboard is the last address of RAM
+    let mut computer = load(&["@24576", "D=M"]);
+    computer.step().unwrap();
+    assert!(computer.step().is_ok());
+}
+
+#[test]
+fn pc_past_rom_faults() {
+    let mut computer = load(&["D=-1", "A=D", "0;JMP"]);
+    for _ in 0..3 {
+        computer.step().unwrap();
+    }
+    assert_eq!(computer.pc, -1);
+    let before = state(&computer);
+    assert_eq!(computer.step(), Err(Fault::PcOutOfBounds(65535)));
+    assert_eq!(state(&computer), before);
+}
+
+#[test]
+fn empty_rom_faults_unless_disabled() {
+    let mut computer = load(&["@3", "D=A"]);
+    computer.step().unwrap();
+    computer.step().unwrap();
+    let before = state(&computer);
+    assert_eq!(computer.step(), Err(Fault::EmptyRom(2)));
+    assert_eq!(state(&computer), before);
+
+    // Like the official CPU emulator, an empty cell then runs as `@0`
+    let mut computer = load(&["@3", "D=A"]);
+    computer.trap_empty_rom = false;
+    computer.a_register = 5;
+    computer.pc = 2;
+    assert!(computer.step().is_ok());
+    assert_eq!((computer.a_register, computer.pc), (0, 3));
+}
+
+#[test]
+fn faults_are_sticky_until_cleared() {
+    let mut computer = load(&["@32767", "M=1", "D=1"]);
+    computer.step().unwrap();
+    assert_eq!(computer.step(), Err(Fault::InvalidAddress(32767)));
+    assert_eq!(computer.fault, Some(Fault::InvalidAddress(32767)));
+
+    // Stepping again reports the same fault without running anything
+    let before = state(&computer);
+    for _ in 0..3 {
+        assert_eq!(computer.step(), Err(Fault::InvalidAddress(32767)));
+    }
+    assert_eq!(state(&computer), before);
+
+    // Once cleared the computer runs again, from wherever the PC now points
+    computer.fault = None;
+    computer.pc = 2;
+    assert!(computer.step().is_ok());
+    assert_eq!((computer.d_register, computer.pc), (1, 3));
+}
+
+#[test]
+fn exit_codes_and_messages() {
+    let faults = [
+        (Fault::InvalidAddress(32767), 3, "invalid memory access at address 32767"),
+        (Fault::PcOutOfBounds(65535), 4, "PC out of bounds: 65535"),
+        (Fault::EmptyRom(2), 5, "executing empty ROM at address 2")
+    ];
+    for (fault, exit_code, message) in faults {
+        assert_eq!(fault.exit_code(), exit_code);
+        assert_eq!(fault.to_string(), message);
+    }
+}
@@ -0,0 +1,36 @@
+use hack_cpu_emulator::format::{character, NumberFormat};
+
+const FORMATS: [NumberFormat; 4] = [
+    NumberFormat::Decimal,
+    NumberFormat::Unsigned,
+    NumberFormat::Hex,
+    NumberFormat::Binary
+];
+
+#[test]
+fn formatted_values_parse_back() {
+    for &format in FORMATS.iter() {
+        for &value in [0, 1, -1, 255, i16::MAX, i16::MIN].iter() {
+            assert_eq!(format.parse(&format.format(value)), Some(value), "{:?} {}", format, value);
+        }
+    }
+}
+
+#[test]
+fn parse_in_each_base() {
+    assert_eq!(NumberFormat::Decimal.parse("-32768"), Some(i16::MIN));
+    assert_eq!(NumberFormat::Decimal.parse("32768"), None);
+    assert_eq!(NumberFormat::Unsigned.parse("65535"), Some(-1));
+    assert_eq!(NumberFormat::Hex.parse("ff"), Some(255));
+    assert_eq!(NumberFormat::Hex.parse("0x8000"), Some(i16::MIN));
+    assert_eq!(NumberFormat::Binary.parse("0b101"), Some(5));
+    assert_eq!(NumberFormat::Binary.parse("102"), None);
+}
+
+#[test]
+fn hack_characters() {
+    assert_eq!(character(65).as_deref(), Some("'A'"));
+    assert_eq!(character(128).as_deref(), Some("newline"));
+    assert_eq!(character(152).as_deref(), Some("F12"));
+    assert_eq!(character(0), None);
+}
@@ -0,0 +1,124 @@
+use hack_cpu_emulator::assembler::assemble;
+use hack_cpu_emulator::computer::{Computer, Fault, ROM_SIZE};
+use hack_cpu_emulator::format::NumberFormat;
+use hack_cpu_emulator::headless::{self, default_dumps, format_dumps, parse_dumps, Dump};
+
+fn load(lines: &[&str]) -> Computer {
+    let source: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    let mut computer = Computer::new();
+    computer.load(&assemble(&source).unwrap().code).unwrap();
+    computer
+}
+
+#[test]
+fn run_until_the_halt_loop() {
+    let mut computer = load(&["@3", "D=A", "@16", "M=D", "(END)", "@END", "0;JMP"]);
+    assert_eq!(headless::run(&mut computer, 100), Ok(true));
+    assert_eq!((computer.pc, computer.cycles), (4, 4));
+    assert_eq!(computer.memory[16], 3);
+
+    // Running again doesn't execute anything
+    assert_eq!(headless::run(&mut computer, 100), Ok(true));
+    assert_eq!(computer.cycles, 4);
+}
+
+#[test]
+fn run_stops_after_max_cycles() {
+    let mut computer = load(&["(LOOP)", "D=D+1", "@LOOP", "0;JMP"]);
+    assert_eq!(headless::run(&mut computer, 10), Ok(false));
+    assert_eq!(computer.cycles, 10);
+}
+
+#[test]
+fn run_stops_on_a_fault() {
+    let mut computer = load(&["@32767", "M=1"]);
+    assert_eq!(headless::run(&mut computer, 10), Err(Fault::InvalidAddress(32767)));
+    assert_eq!(computer.cycles, 1);
+}
+
+#[test]
+fn run_with_executes_each_instruction_through_step() {
+    let mut computer = load(&["@2", "D=A", "(END)", "@END", "0;JMP"]);
+    let mut pcs = vec![];
+    let halted = headless::run_with(&mut computer, 100, |computer| {
+        pcs.push(computer.pc);
+        computer.step()
+    });
+    assert_eq!(halted, Ok(true));
+    assert_eq!(pcs, vec![0, 1]);
+}
+
+#[test]
+fn halt_loops() {
+    // `@END` followed by `0;JMP`, halted on either instruction
+    let mut computer = load(&["(END)", "@END", "0;JMP"]);
+    assert!(computer.is_halted());
+    computer.step().unwrap();
+    assert!(computer.is_halted());
+
+    // A lone `0;JMP` with A already pointing at it
+    let mut computer = load(&["@1", "0;JMP"]);
+    assert!(!computer.is_halted());
+    computer.step().unwrap();
+    assert!(computer.is_halted());
+
+    // ...but not when A points elsewhere
+    let mut computer = load(&["@5", "0;JMP"]);
+    computer.step().unwrap();
+    assert!(!computer.is_halted());
+
+    // A conditional jump isn't a halt loop
+    let computer = load(&["(END)", "@END", "D;JEQ"]);
+    assert!(!computer.is_halted());
+}
+
+#[test]
+fn halt_check_at_the_end_of_rom() {
+    let mut computer = Computer::new();
+    let last = (ROM_SIZE - 1) as i16;
+    computer.rom[ROM_SIZE - 1] = Some(last);
+    computer.pc = last;
+    assert!(!computer.is_halted());
+}
+
+#[test]
+fn parse_dump_lists() {
+    assert_eq!(
+        parse_dumps("A, D,PC,RAM[3],RAM[256..260]"),
+        Ok(vec![Dump::ARegister, Dump::DRegister, Dump::Pc, Dump::Memory(3..4), Dump::Memory(256..260)])
+    );
+    assert_eq!(parse_dumps("RAM[24576],RAM[24570..24577]"), Ok(vec![Dump::Memory(24576..24577), Dump::Memory(24570..24577)]));
+    assert_eq!(default_dumps(), vec![Dump::ARegister, Dump::DRegister, Dump::Pc, Dump::Memory(0..16)]);
+
+    for spec in [
+        "M", "RAM[x]", "RAM[1", "RAM[4..4]", "RAM[5..2]", "RAM[24577]", "RAM[0..24578]",
+        "RAM[18446744073709551615]", "RAM[0..1000000000]", "A,,D"
+    ] {
+        assert!(parse_dumps(spec).unwrap_err().starts_with("invalid dump: "), "{}", spec);
+    }
+}
+
+#[test]
+fn format_dump_lists() {
+    let mut computer = Computer::new();
+    computer.a_register = 16;
+    computer.d_register = -1;
+    computer.pc = 7;
+    computer.memory[16] = 255;
+    let dumps = parse_dumps("A,D,PC,RAM[15..17]").unwrap();
+
+    assert_eq!(format_dumps(&computer, &dumps, NumberFormat::Decimal), "\
+A: 16
+D: -1
+PC: 7
+RAM[15]: 0
+RAM[16]: 255
+");
+    assert_eq!(format_dumps(&computer, &dumps, NumberFormat::Hex), "\
+A: 0x0010
+D: 0xFFFF
+PC: 0x0007
+RAM[15]: 0x0000
+RAM[16]: 0x00FF
+");
+}
@@ -0,0 +1,97 @@
+use hack_cpu_emulator::assembler::assemble;
+use hack_cpu_emulator::computer::{Computer, Fault};
+
+fn load(lines: &[&str]) -> Computer {
+    let source: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    let mut computer = Computer::new();
+    computer.load(&assemble(&source).unwrap().code).unwrap();
+    computer
+}
+
+#[test]
+fn step_back_restores_registers_and_memory() {
+    let mut computer = load(&["@21", "D=A", "@16", "M=D", "D=D+1", "@5", "0;JMP"]);
+    computer.memory[16] = -7;
+    for _ in 0..7 {
+        computer.step().unwrap();
+    }
+    assert_eq!((computer.a_register, computer.d_register, computer.pc), (5, 22, 5));
+
+    // Undo the jump, `@5` and `D=D+1`
+    for _ in 0..3 {
+        assert!(computer.step_back());
+    }
+    assert_eq!((computer.a_register, computer.d_register, computer.pc), (16, 21, 4));
+    assert_eq!(computer.memory[16], 21);
+    assert_eq!(computer.cycles, 4);
+
+    // Undoing the write puts back the value it overwrote
+    assert!(computer.step_back());
+    assert_eq!(computer.memory[16], -7);
+    assert_eq!((computer.a_register, computer.d_register, computer.pc), (16, 21, 3));
+
+    while computer.step_back() {}
+    assert_eq!((computer.a_register, computer.d_register, computer.pc), (0, 0, 0));
+    assert_eq!(computer.cycles, 0);
+}
+
+#[test]
+fn history_size_evicts_the_oldest_snapshots() {
+    let mut computer = load(&["(LOOP)", "D=D+1", "@LOOP", "0;JMP"]);
+    computer.set_history_size(4);
+    for _ in 0..10 {
+        computer.step().unwrap();
+    }
+    assert_eq!(computer.oldest_cycle(), 6);
+
+    // Shrinking the history drops the oldest snapshots first
+    computer.set_history_size(2);
+    assert_eq!(computer.oldest_cycle(), 8);
+    assert!(computer.step_back());
+    assert!(computer.step_back());
+    assert!(!computer.step_back());
+    assert_eq!(computer.cycles, 8);
+
+    computer.set_history_size(0);
+    computer.step().unwrap();
+    assert_eq!(computer.oldest_cycle(), 9);
+    assert!(!computer.step_back());
+}
+
+#[test]
+fn rewind_to_a_recorded_cycle() {
+    let mut computer = load(&["(LOOP)", "D=D+1", "@LOOP", "0;JMP"]);
+    computer.set_history_size(5);
+    for _ in 0..9 {
+        computer.step().unwrap();
+    }
+    assert_eq!(computer.d_register, 3);
+
+    // Cycles in the future or older than the history are refused
+    assert!(!computer.rewind_to(10));
+    assert!(!computer.rewind_to(3));
+    assert_eq!(computer.cycles, 9);
+
+    assert!(computer.rewind_to(4));
+    assert_eq!((computer.cycles, computer.pc, computer.d_register), (4, 1, 2));
+    assert!(computer.rewind_to(4));
+    assert_eq!(computer.cycles, 4);
+}
+
+#[test]
+fn step_back_clears_a_fault_only_when_it_undoes_an_instruction() {
+    let mut computer = load(&["@32767", "M=1"]);
+    computer.step().unwrap();
+    assert_eq!(computer.step(), Err(Fault::InvalidAddress(32767)));
+
+    computer.set_history_size(0);
+    assert!(!computer.step_back());
+    assert_eq!(computer.fault, Some(Fault::InvalidAddress(32767)));
+
+    let mut computer = load(&["@32767", "M=1"]);
+    computer.step().unwrap();
+    assert!(computer.step().is_err());
+    assert!(computer.step_back());
+    assert_eq!(computer.fault, None);
+    assert_eq!((computer.pc, computer.cycles), (0, 0));
+}
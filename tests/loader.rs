@@ -0,0 +1,66 @@
+use std::fs;
+use std::path::PathBuf;
+
+use hack_cpu_emulator::assembler::assemble;
+use hack_cpu_emulator::loader::{load_program, load_program_with_source, LoadError};
+
+/// Writes `contents` to a file of the given name in a fresh directory.
+fn write_file(name: &str, contents: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hack-loader-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn load_hack_file() {
+    let path = write_file("Add.hack", "0000000000000010\n1110110000010000\n\n  0000000000000011  \n");
+    let program = load_program_with_source(&path).unwrap();
+    assert_eq!(program.code, vec![2, 0b1110110000010000u16 as i16, 3]);
+    assert!(program.source.is_none());
+
+    // Without an extension, binary content is detected
+    let path = write_file("Add", "0000000000000010\n1110110000010000\n");
+    assert_eq!(load_program(&path).unwrap(), vec![2, 0b1110110000010000u16 as i16]);
+}
+
+#[test]
+fn invalid_binary_lines() {
+    let path = write_file("Short.hack", "0000000000000010\n\n111011000001000\n");
+    match load_program(&path) {
+        Err(LoadError::InvalidBinary { line: 3, content }) => assert_eq!(content, "111011000001000"),
+        result => panic!("unexpected {:?}", result)
+    }
+
+    let path = write_file("Digit.hack", "0000000000000010\n1110110000012000\n");
+    match load_program(&path) {
+        Err(error @ LoadError::InvalidBinary { line: 2, .. }) => assert_eq!(
+            error.to_string(),
+            "invalid binary instruction on line 2: \"1110110000012000\" (expected 16 '0' or '1' characters)"
+        ),
+        result => panic!("unexpected {:?}", result)
+    }
+
+    // A file with some assembly in it isn't detected as binary, so it's assembled
+    let path = write_file("Mixed", "0000000000000010\n@2\n");
+    match load_program(&path) {
+        Err(LoadError::Assembly { errors, .. }) => assert_eq!(errors[0].line, 1),
+        result => panic!("unexpected {:?}", result)
+    }
+}
+
+#[test]
+fn asm_files_are_never_binary() {
+    // `0` and `1` are valid comps, and `.asm` files are always assembled
+    let path = write_file("Constants.asm", "0\n1\n");
+    let program = load_program_with_source(&path).unwrap();
+    assert_eq!(program.code, assemble(&["0".to_owned(), "1".to_owned()]).unwrap().code);
+    assert!(program.source.is_some());
+
+    let path = write_file("Binary.asm", "0000000000000010\n");
+    match load_program(&path) {
+        Err(LoadError::Assembly { errors, .. }) => assert_eq!(errors.len(), 1),
+        result => panic!("unexpected {:?}", result)
+    }
+}
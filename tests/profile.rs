@@ -0,0 +1,91 @@
+use hack_cpu_emulator::assembler::assemble;
+use hack_cpu_emulator::computer::Computer;
+use hack_cpu_emulator::headless;
+use hack_cpu_emulator::profile::{by_label, hot_spots, report, LabelProfile};
+
+fn source(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|line| line.to_string()).collect()
+}
+
+#[test]
+fn count_executions() {
+    let assembly = assemble(&source(&["@2", "D=A", "(LOOP)", "D=D-1", "@LOOP", "D;JGT", "(END)", "@END", "0;JMP"])).unwrap();
+    let mut computer = Computer::new();
+    assert_eq!(computer.profile(), None);
+    computer.set_profiling(true);
+    computer.load(&assembly.code).unwrap();
+    assert_eq!(headless::run(&mut computer, 100), Ok(true));
+
+    let profile = computer.profile().unwrap();
+    assert_eq!(&profile[..6], &[1, 1, 2, 2, 2, 0]);
+    assert_eq!(profile.iter().sum::<u64>(), computer.cycles);
+
+    // Stepping back undoes the count
+    computer.step_back();
+    assert_eq!(computer.profile().unwrap()[4], 1);
+
+    computer.set_profiling(false);
+    assert_eq!(computer.profile(), None);
+    assert_eq!(computer.memory_profile(), None);
+}
+
+#[test]
+fn count_memory_accesses() {
+    let assembly = assemble(&source(&["@16", "M=1", "D=M", "M=D+M", "@17", "D=D+A", "AM=D"])).unwrap();
+    let mut computer = Computer::new();
+    computer.set_profiling(true);
+    computer.load(&assembly.code).unwrap();
+    for _ in 0..7 {
+        computer.step().unwrap();
+    }
+
+    let counts = computer.memory_profile().unwrap();
+    assert_eq!((counts.reads[16], counts.writes[16]), (2, 2));
+    assert_eq!((counts.reads[17], counts.writes[17]), (0, 1));
+    assert_eq!(counts.reads.iter().sum::<u64>() + counts.writes.iter().sum::<u64>(), 5);
+
+    // Stepping back undoes reads as well as writes
+    let before = counts.clone();
+    computer.rewind_to(3);
+    let counts = computer.memory_profile().unwrap();
+    assert_eq!((counts.reads[16], counts.writes[16]), (1, 1));
+    assert_eq!(counts.writes[17], 0);
+    for _ in 0..4 {
+        computer.step().unwrap();
+    }
+    assert_eq!(computer.memory_profile(), Some(&before));
+}
+
+#[test]
+fn hot_spots_and_labels() {
+    let assembly = assemble(&source(&[
+        "@Main.run", "0;JMP",
+        "(Sys.init)", "@0", "@1",
+        "(Main.run)", "@0",
+        "(Main.run$LOOP)", "@Main.run$LOOP", "0;JMP"
+    ])).unwrap();
+    let profile = vec![1, 1, 0, 0, 1, 4, 4, 0];
+
+    assert_eq!(hot_spots(&profile), vec![(5, 4), (6, 4), (0, 1), (1, 1), (4, 1)]);
+    // `Sys.init` never ran, and `Main.run$LOOP` counts toward `Main.run`
+    assert_eq!(by_label(&profile, &assembly.symbols), vec![
+        LabelProfile { name: "Main.run".to_owned(), address: 4, cycles: 9 },
+        LabelProfile { name: "(start)".to_owned(), address: 0, cycles: 2 }
+    ]);
+
+    let mut rom = vec![None; 8];
+    for (i, &instr) in assembly.code.iter().enumerate() {
+        rom[i] = Some(instr);
+    }
+    let report = report(&profile, &rom, &assembly.symbols, 1);
+    assert_eq!(report, "\
+11 cycles
+
+    cycles       %  label
+         9   81.8%  Main.run
+         2   18.2%  (start)
+
+     count       %  address  instruction
+         4   36.4%        5  @5
+");
+}
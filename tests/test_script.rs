@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::PathBuf;
+
+use hack_cpu_emulator::test_script::{run_test_script, TestError};
+
+const MULT_ASM: &str = "\
+// Multiplies R0 and R1 and stores the result in R2.
+    @R2
+    M=0
+(LOOP)
+    @R1
+    D=M
+    @END
+    D;JEQ
+    @R0
+    D=M
+    @R2
+    M=D+M
+    @R1
+    M=M-1
+    @LOOP
+    0;JMP
+(END)
+    @END
+    0;JMP
+";
+
+const MULT_TST: &str = "\
+load Mult.asm,
+output-file Mult.out,
+compare-to Mult.cmp,
+output-list RAM[0]%D2.6.2 RAM[2]%D1.6.1 RAM[2]%B1.16.1 RAM[2]%X1.4.1;
+echo \"Multiplying\";
+
+set RAM[0] 3,   // Test arguments
+set RAM[1] 5,
+set RAM[2] -1;  /* The program must clear the product */
+repeat 100 {
+  ticktock;
+}
+output;
+
+set PC 0,
+set RAM[0] -2,
+set RAM[1] 4;
+while PC < 14 {
+  ticktock;
+}
+output;
+";
+
+const MULT_CMP: &str = "\
+|  RAM[0]  | RAM[2] |      RAM[2]      |RAM[2]|
+|       3  |     15 | 0000000000001111 | 000F |
+|      -2  |     -8 | 1111111111111000 | FFF8 |
+";
+
+/// Writes the files of a test to a fresh directory and returns its path.
+fn fixture(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hack-test-script-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, contents) in files {
+        fs::write(dir.join(file), contents).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn passing_script() {
+    let dir = fixture("pass", &[("Mult.asm", MULT_ASM), ("Mult.tst", MULT_TST), ("Mult.cmp", MULT_CMP)]);
+    let mut echoes = vec![];
+    run_test_script(&dir.join("Mult.tst"), |text| echoes.push(text.to_owned())).unwrap();
+
+    assert_eq!(echoes, vec!["Multiplying"]);
+    assert_eq!(fs::read_to_string(dir.join("Mult.out")).unwrap(), MULT_CMP);
+}
+
+#[test]
+fn comparison_failure() {
+    let cmp = MULT_CMP.replace("-8 | 1111111111111000 | FFF8", "-6 | 1111111111111010 | FFFA");
+    let dir = fixture("fail", &[("Mult.asm", MULT_ASM), ("Mult.tst", MULT_TST), ("Mult.cmp", &cmp)]);
+    match run_test_script(&dir.join("Mult.tst"), |_| {}) {
+        Err(TestError::ComparisonFailure { line: 3 }) => {}
+        result => panic!("unexpected {:?}", result)
+    }
+    // The output up to the failing line is still written
+    assert_eq!(fs::read_to_string(dir.join("Mult.out")).unwrap(), MULT_CMP);
+}
+
+#[test]
+fn script_errors() {
+    let dir = fixture("errors", &[("Mult.asm", MULT_ASM), ("Bad.tst", "load Mult.asm,\nfrobnicate;\n")]);
+    match run_test_script(&dir.join("Bad.tst"), |_| {}) {
+        Err(TestError::Syntax { line: 2, .. }) => {}
+        result => panic!("unexpected {:?}", result)
+    }
+
+    let dir = fixture("missing", &[("Missing.tst", "load Missing.asm;\n")]);
+    match run_test_script(&dir.join("Missing.tst"), |_| {}) {
+        Err(TestError::Runtime { line: 1, .. }) => {}
+        result => panic!("unexpected {:?}", result)
+    }
+}
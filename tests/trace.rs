@@ -0,0 +1,56 @@
+use hack_cpu_emulator::assembler::assemble;
+use hack_cpu_emulator::computer::Computer;
+use hack_cpu_emulator::headless;
+use hack_cpu_emulator::trace::{MemoryWrite, TraceEntry, TraceFormat, TraceReader, TraceWriter};
+
+fn run_traced(format: TraceFormat) -> Vec<u8> {
+    let source: Vec<String> = ["@5", "D=A", "@16", "M=D", "M=M+1", "(END)", "@END", "0;JMP"]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+    let mut computer = Computer::new();
+    computer.load(&assemble(&source).unwrap().code).unwrap();
+    computer.memory[16] = -7;
+
+    let mut out = vec![];
+    let mut trace = TraceWriter::new(&mut out, format).unwrap();
+    assert_eq!(headless::run_with(&mut computer, 100, |computer| trace.step(computer)), Ok(true));
+    trace.finish().unwrap();
+    out
+}
+
+#[test]
+fn text_trace() {
+    let text = String::from_utf8(run_traced(TraceFormat::Text)).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[1], "       1     1  D=A              A=5      D=5");
+    assert_eq!(lines[3], "       3     3  M=D              A=16     D=5      RAM[16]: -7 -> 5");
+}
+
+#[test]
+fn binary_trace_round_trip() {
+    let binary = run_traced(TraceFormat::Binary);
+    let entries: Vec<TraceEntry> = TraceReader::new(&binary[..]).unwrap().map(Result::unwrap).collect();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[4], TraceEntry {
+        cycle: 4,
+        pc: 4,
+        instruction: assemble(&["M=M+1".to_owned()]).unwrap().code[0],
+        a_register: 16,
+        d_register: 5,
+        write: Some(MemoryWrite { address: 16, old: 5, new: 6 })
+    });
+
+    let text: Vec<String> = entries.iter().map(TraceEntry::to_string).collect();
+    let expected = String::from_utf8(run_traced(TraceFormat::Text)).unwrap();
+    assert_eq!(text.join("\n") + "\n", expected);
+}
+
+#[test]
+fn reject_bad_traces() {
+    assert!(TraceReader::new(&b"HTRX\x01"[..]).is_err());
+    let mut binary = run_traced(TraceFormat::Binary);
+    binary.truncate(binary.len() - 3);
+    assert!(TraceReader::new(&binary[..]).unwrap().any(|entry| entry.is_err()));
+}
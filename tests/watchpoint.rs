@@ -0,0 +1,52 @@
+use hack_cpu_emulator::assembler::assemble;
+use hack_cpu_emulator::computer::{Computer, MemoryAccess};
+use hack_cpu_emulator::watchpoint::{AccessKind, Comparison, WatchHit, Watchpoint};
+
+fn no_symbols(_: &str) -> Option<u16> {
+    None
+}
+
+#[test]
+fn parse_specs() {
+    let watchpoint = Watchpoint::parse("256", no_symbols).unwrap();
+    assert_eq!(watchpoint, Watchpoint { kind: AccessKind::Write, addresses: 256..257, condition: None });
+
+    let watchpoint = Watchpoint::parse("rw 0x100..260 <= -1", no_symbols).unwrap();
+    assert_eq!(watchpoint.kind, AccessKind::ReadWrite);
+    assert_eq!(watchpoint.addresses, 256..260);
+    assert_eq!(watchpoint.condition, Some((Comparison::Le, -1)));
+    assert_eq!(watchpoint.to_string(), "rw RAM[256..260] <= -1");
+
+    let symbol = |name: &str| if name == "sum" { Some(17) } else { None };
+    let watchpoint = Watchpoint::parse("r sum=0", symbol).unwrap();
+    assert_eq!(watchpoint.to_string(), "r RAM[17] == 0");
+
+    assert!(Watchpoint::parse("x 1", no_symbols).is_err());
+    assert!(Watchpoint::parse("260..256", no_symbols).is_err());
+    assert!(Watchpoint::parse("30000", no_symbols).is_err());
+    assert!(Watchpoint::parse("1 ~ 2", no_symbols).is_err());
+}
+
+#[test]
+fn step_reports_memory_access() {
+    let program = assemble(&["@5".to_owned(), "M=M+1".to_owned(), "D=A".to_owned()]).unwrap();
+    let mut computer = Computer::new();
+    computer.load(&program.code).unwrap();
+    computer.memory[5] = 41;
+
+    assert_eq!(computer.step(), Ok(MemoryAccess::default()));
+    assert_eq!(computer.step(), Ok(MemoryAccess { read: Some((5, 41)), write: Some((5, 42)) }));
+    assert_eq!(computer.step(), Ok(MemoryAccess::default()));
+}
+
+#[test]
+fn check_access_kind_range_and_condition() {
+    let access = MemoryAccess { read: Some((5, 41)), write: Some((5, 42)) };
+    let check = |spec: &str| Watchpoint::parse(spec, no_symbols).unwrap().check(&access);
+
+    assert_eq!(check("w 5"), Some(WatchHit { kind: AccessKind::Write, address: 5, value: 42 }));
+    assert_eq!(check("r 5"), Some(WatchHit { kind: AccessKind::Read, address: 5, value: 41 }));
+    assert_eq!(check("rw 0..10 < 42"), Some(WatchHit { kind: AccessKind::Read, address: 5, value: 41 }));
+    assert_eq!(check("w 6..10"), None);
+    assert_eq!(check("w 5 != 42"), None);
+}